@@ -10,12 +10,33 @@ interface GameStateWithHistory {
   history: HistoryData;
 }
 
+type Termination =
+  | 'Checkmate'
+  | 'Stalemate'
+  | 'GeneralCaptured'
+  | 'Resignation'
+  | 'Timeout'
+  | 'AgreedDraw'
+  | 'PerpetualCheck'
+  | 'PerpetualChase'
+  | 'Repetition'
+  | 'MoveLimit'
+  | 'InsufficientMaterial';
+
+interface GameResult {
+  winner: 'Red' | 'Black' | null;
+  termination: Termination;
+}
+
 interface GameState {
   board: any;
   current_turn: 'Red' | 'Black';
   is_in_check: boolean;
   is_ended: boolean;
   winner: 'Red' | 'Black' | null;
+  result: GameResult | null;
+  no_capture_plies: number;
+  move_number: number;
 }
 
 interface HistoryData {
@@ -41,17 +62,52 @@ interface MoveRecord {
     piece_type: string;
     color: 'Red' | 'Black';
   } | null;
+  annotation: Annotation;
+}
+
+type MarkColor = 'Green' | 'Red' | 'Yellow' | 'Blue';
+
+interface Annotation {
+  comment: string | null;
+  // PGN glyph numbers: 1 is !, 2 is ?, 5 is !? ...
+  nags: number[];
+  arrows: { from_x: number; from_y: number; to_x: number; to_y: number; color: MarkColor }[];
+  highlights: { x: number; y: number; color: MarkColor }[];
 }
 
+const NAG_SYMBOLS: Record<number, string> = { 1: '!', 2: '?', 3: '!!', 4: '??', 5: '!?', 6: '?!' };
+
+// The move evaluation symbols to show after a move, e.g. "!?"
+const evaluation = (record: MoveRecord) =>
+  (record.annotation?.nags ?? []).map(nag => NAG_SYMBOLS[nag] ?? '').join('');
+
+type RuleSet = 'Asian' | 'Chinese';
+
 const App: React.FC = () => {
   const [gameState, setGameState] = useState<GameStateWithHistory | null>(null);
   const [moveHistory, setMoveHistory] = useState<string[]>([]);
+  const [ruleSet, setRuleSet] = useState<RuleSet>('Asian');
+  const [checkers, setCheckers] = useState<[number, number][]>([]);
 
   useEffect(() => {
     // Initialize game
     initGame();
+    invoke<RuleSet>('get_rule_set')
+      .then(setRuleSet)
+      .catch(error => console.error('Error getting rule set:', error));
   }, []);
 
+  useEffect(() => {
+    // Mark the pieces giving check after every change of position
+    if (!gameState?.game_state.is_in_check) {
+      setCheckers([]);
+      return;
+    }
+    invoke<[number, number][]>('get_checkers')
+      .then(setCheckers)
+      .catch(error => console.error('Error getting checkers:', error));
+  }, [gameState]);
+
   const initGame = async () => {
     try {
       const state = await invoke<GameStateWithHistory>('new_game');
@@ -94,7 +150,10 @@ const App: React.FC = () => {
           current_turn: 'Red',
           is_in_check: false,
           is_ended: false,
-          winner: null
+          winner: null,
+          result: null,
+          no_capture_plies: 0,
+          move_number: 1
         },
         history: { rounds: [] }
       });
@@ -138,6 +197,38 @@ const App: React.FC = () => {
     }
   };
 
+  const handleRedoMove = async () => {
+    try {
+      const state = await invoke<GameStateWithHistory>('redo_move');
+      setGameState(state);
+      updateHistoryFromState(state);
+    } catch (error) {
+      console.error('Error redoing move:', error);
+    }
+  };
+
+  const handleNavigate = async (command: string) => {
+    try {
+      const state = await invoke<GameStateWithHistory>(command);
+      setGameState(state);
+      updateHistoryFromState(state);
+    } catch (error) {
+      console.error('Error navigating the game:', error);
+    }
+  };
+
+  const handleRuleSetChange = async (rules: RuleSet) => {
+    try {
+      // Repetitions in the current game are judged by the new rules from now on
+      const state = await invoke<GameStateWithHistory>('set_rule_set', { ruleSet: rules });
+      setRuleSet(rules);
+      setGameState(state);
+      updateHistoryFromState(state);
+    } catch (error) {
+      console.error('Error setting rule set:', error);
+    }
+  };
+
   const handleGetValidMoves = async (x: number, y: number): Promise<[number, number][]> => {
     try {
       // 验证：只能获取自己颜色棋子的有效移动
@@ -154,82 +245,32 @@ const App: React.FC = () => {
     }
   };
 
-  const getPieceName = (piece: { piece_type: string; color: string }) => {
-    const chineseNames: Record<string, Record<string, string>> = {
-      General: { Red: '帅', Black: '将' },
-      Advisor: { Red: '仕', Black: '士' },
-      Elephant: { Red: '相', Black: '象' },
-      Horse: { Red: '马', Black: '马' },
-      Chariot: { Red: '车', Black: '车' },
-      Cannon: { Red: '炮', Black: '炮' },
-      Soldier: { Red: '兵', Black: '卒' }
-    };
-    return chineseNames[piece.piece_type][piece.color];
-  };
-
-
-
-  const updateHistoryFromState = (state: GameStateWithHistory) => {
+  const updateHistoryFromState = async (state: GameStateWithHistory) => {
     if (!state.history || !state.history.rounds) {
       setMoveHistory([]);
       return;
     }
-    
-    const historyStrings: string[] = [];
-    
-    state.history.rounds.forEach(round => {
-      const redMoveStr = convertMoveRecordToNotation(round.red_move);
-      
-      if (round.black_move) {
-        const blackMoveStr = convertMoveRecordToNotation(round.black_move);
-        historyStrings.push(`${round.round_number}. ${redMoveStr} ${blackMoveStr}`);
-      } else {
-        historyStrings.push(`${round.round_number}. ${redMoveStr}`);
-      }
-    });
-    
-    setMoveHistory(historyStrings);
-  };
 
-  const convertMoveRecordToNotation = (move: MoveRecord): string => {
-    if (!move.piece) {
-      // Fallback: try to get piece from current board state (old behavior)
-      if (!gameState) return '';
-      const piece = gameState.game_state.board.cells[move.from_y][move.from_x];
-      if (!piece) return '';
-      return getPieceName(piece) + '?'; // Add question mark to indicate potential inaccuracy
-    }
-    
-    const pieceName = getPieceName(move.piece);
-    const colNum = move.piece.color === 'Red' ? (9 - move.from_x) : (move.from_x + 1);
-    
-    const dx = move.to_x - move.from_x;
-    const dy = move.to_y - move.from_y;
-    
-    let action: string;
-    let target: string;
-    
-    if (dx === 0) {
-      action = move.piece.color === 'Red' ? 
-        (dy > 0 ? '退' : '进') : 
-        (dy < 0 ? '退' : '进');
-      target = Math.abs(dy).toString();
-    } else {
-      const targetColNum = move.piece.color === 'Red' ? (9 - move.to_x) : (move.to_x + 1);
-      
-      if (dy === 0) {
-        action = '平';
-        target = targetColNum.toString();
-      } else if ((move.piece.color === 'Red' && dy < 0) || (move.piece.color === 'Black' && dy > 0)) {
-        action = '进';
-        target = targetColNum.toString();
-      } else {
-        action = '退';
-        target = targetColNum.toString();
-      }
+    try {
+      // One entry per move in Chinese notation, e.g. 炮二平五
+      const notations = await invoke<string[]>('get_move_list');
+      let ply = 0;
+      const historyStrings = state.history.rounds.map(round => {
+        // A round opened by Black has a placeholder red move with from == to
+        const redMove = round.red_move;
+        const hasRedMove = redMove.from_x !== redMove.to_x || redMove.from_y !== redMove.to_y;
+        const redMoveStr = hasRedMove ? notations[ply++] + evaluation(redMove) : '...';
+
+        if (round.black_move) {
+          return `${round.round_number}. ${redMoveStr} ${notations[ply++]}${evaluation(round.black_move)}`;
+        }
+        return `${round.round_number}. ${redMoveStr}`;
+      });
+
+      setMoveHistory(historyStrings);
+    } catch (error) {
+      console.error('Error getting move list:', error);
     }
-    
-    return `${pieceName}${colNum}${action}${target}`;
   };
 
   if (!gameState) {
@@ -249,6 +290,7 @@ const App: React.FC = () => {
              isInCheck={gameState.game_state.is_in_check}
              isEnded={gameState.game_state.is_ended}
              winner={gameState.game_state.winner}
+             termination={gameState.game_state.result?.termination ?? null}
            />
            
            <ChessBoard
@@ -256,11 +298,16 @@ const App: React.FC = () => {
              onPieceClick={handleGetValidMoves}
              onMove={handleMakeMove}
              isEnded={gameState.game_state.is_ended} // 传递游戏结束状态
+             checkers={checkers}
            />
           
           <ControlPanel
             onNewGame={initGame}
             onUndo={handleUndoMove}
+            onRedo={handleRedoMove}
+            onNavigate={handleNavigate}
+            ruleSet={ruleSet}
+            onRuleSetChange={handleRuleSetChange}
           />
           
           <HistoryPanel history={moveHistory} />
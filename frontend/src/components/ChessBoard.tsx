@@ -12,13 +12,15 @@ interface ChessBoardProps {
   onPieceClick: (x: number, y: number) => Promise<[number, number][]>;
   onMove: (fromX: number, fromY: number, toX: number, toY: number) => void;
   isEnded: boolean; // 新增属性
+  checkers: [number, number][]; // 正在将军的棋子
 }
 
 const ChessBoard: React.FC<ChessBoardProps> = ({ 
   board, 
   onPieceClick, 
   onMove,
-  isEnded
+  isEnded,
+  checkers
 }) => {
   const [selectedPiece, setSelectedPiece] = useState<[number, number] | null>(null);
   const [validMoves, setValidMoves] = useState<[number, number][]>([]);
@@ -60,6 +62,10 @@ const ChessBoard: React.FC<ChessBoardProps> = ({
     return validMoves.some(([mx, my]) => mx === x && my === y);
   };
 
+  const isChecker = (x: number, y: number) => {
+    return checkers.some(([cx, cy]) => cx === x && cy === y);
+  };
+
   const getPieceCharacter = (piece: any) => {
     if (!piece) return '';
     
@@ -161,6 +167,8 @@ const ChessBoard: React.FC<ChessBoardProps> = ({
                   isSelected(x, y) ? 'selected' : ''
                 } ${
                   isValidMove(x, y) ? 'valid-move' : ''
+                } ${
+                  isChecker(x, y) ? 'checker' : ''
                 }`}
                 style={{
                   left: `${x * 50 - 20}px`,
@@ -4,11 +4,19 @@ import './ControlPanel.css';
 interface ControlPanelProps {
   onNewGame: () => void;
   onUndo: () => void;
+  onRedo: () => void;
+  onNavigate: (command: 'goto_first' | 'goto_previous' | 'goto_next' | 'goto_last') => void;
+  ruleSet: 'Asian' | 'Chinese';
+  onRuleSetChange: (ruleSet: 'Asian' | 'Chinese') => void;
 }
 
 const ControlPanel: React.FC<ControlPanelProps> = ({
   onNewGame,
-  onUndo
+  onUndo,
+  onRedo,
+  onNavigate,
+  ruleSet,
+  onRuleSetChange
 }) => {
   return (
     <div className="control-panel">
@@ -18,6 +26,32 @@ const ControlPanel: React.FC<ControlPanelProps> = ({
       <button onClick={onUndo} className="control-button undo">
         悔棋
       </button>
+      <button onClick={onRedo} className="control-button redo">
+        重做
+      </button>
+      <div className="replay-controls">
+        <button onClick={() => onNavigate('goto_first')} className="control-button replay" title="开局">
+          ⏮
+        </button>
+        <button onClick={() => onNavigate('goto_previous')} className="control-button replay" title="上一步">
+          ◀
+        </button>
+        <button onClick={() => onNavigate('goto_next')} className="control-button replay" title="下一步">
+          ▶
+        </button>
+        <button onClick={() => onNavigate('goto_last')} className="control-button replay" title="终局">
+          ⏭
+        </button>
+      </div>
+      <select
+        value={ruleSet}
+        onChange={(e) => onRuleSetChange(e.target.value as 'Asian' | 'Chinese')}
+        className="rule-set-select"
+        title="长将、长捉的判罚规则"
+      >
+        <option value="Asian">亚洲规则</option>
+        <option value="Chinese">中国规则</option>
+      </select>
     </div>
   );
 };
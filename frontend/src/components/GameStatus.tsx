@@ -6,13 +6,29 @@ interface GameStatusProps {
   isInCheck: boolean;
   isEnded: boolean;
   winner: 'Red' | 'Black' | null;
+  termination: string | null;
 }
 
+const terminationNames: Record<string, string> = {
+  Checkmate: '将死',
+  Stalemate: '困毙',
+  GeneralCaptured: '吃将',
+  Resignation: '认输',
+  Timeout: '超时',
+  AgreedDraw: '议和',
+  PerpetualCheck: '长将判负',
+  PerpetualChase: '长捉判负',
+  Repetition: '循环作和',
+  MoveLimit: '自然限着作和',
+  InsufficientMaterial: '双方无攻击子力作和'
+};
+
 const GameStatus: React.FC<GameStatusProps> = ({
   currentTurn,
   isInCheck,
   isEnded,
-  winner
+  winner,
+  termination
 }) => {
   const getTurnColor = () => {
     return currentTurn === 'Red' ? 'red' : 'black';
@@ -23,9 +39,16 @@ const GameStatus: React.FC<GameStatusProps> = ({
       {isEnded ? (
         <div className="game-ended">
           <h2>游戏结束</h2>
-          <div className="winner">
-            获胜方: <span className={winner?.toLowerCase()}>{winner}</span>
-          </div>
+          {winner ? (
+            <div className="winner">
+              获胜方: <span className={winner.toLowerCase()}>{winner}</span>
+            </div>
+          ) : (
+            <div className="winner">和棋</div>
+          )}
+          {termination && (
+            <div className="termination">{terminationNames[termination] ?? termination}</div>
+          )}
         </div>
       ) : (
         <div className="game-playing">
@@ -0,0 +1,203 @@
+use crate::game::GameStateManager;
+use crate::tree::NodeId;
+use crate::ChessError;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Notes attached to a move for analysis or teaching.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Annotation {
+    pub comment: Option<String>,
+    /// Evaluation symbols, in the order they were given
+    pub nags: Vec<Nag>,
+    pub arrows: Vec<Arrow>,
+    pub highlights: Vec<Highlight>,
+}
+
+impl Annotation {
+    pub fn is_empty(&self) -> bool {
+        self.comment.is_none()
+            && self.nags.is_empty()
+            && self.arrows.is_empty()
+            && self.highlights.is_empty()
+    }
+}
+
+/// A numeric annotation glyph with the numbering PGN uses: $1 is "!", $2 is
+/// "?", $14 is "⩲" (Red is slightly better) and so on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Nag(pub u8);
+
+// Glyphs with a symbol, the first spelling of each being the one shown
+const SYMBOLS: [(u8, &[&str]); 14] = [
+    (1, &["!"]),
+    (2, &["?"]),
+    (3, &["!!"]),
+    (4, &["??"]),
+    (5, &["!?"]),
+    (6, &["?!"]),
+    (10, &["="]),
+    (13, &["∞"]),
+    (14, &["⩲", "+="]),
+    (15, &["⩱", "=+"]),
+    (16, &["±", "+/-"]),
+    (17, &["∓", "-/+"]),
+    (18, &["+-"]),
+    (19, &["-+"]),
+];
+
+impl Nag {
+    pub const GOOD: Nag = Nag(1);
+    pub const MISTAKE: Nag = Nag(2);
+    pub const BRILLIANT: Nag = Nag(3);
+    pub const BLUNDER: Nag = Nag(4);
+    pub const INTERESTING: Nag = Nag(5);
+    pub const DUBIOUS: Nag = Nag(6);
+
+    /// The symbol of the glyph, if it has one.
+    pub fn symbol(self) -> Option<&'static str> {
+        SYMBOLS
+            .iter()
+            .find(|(code, _)| *code == self.0)
+            .map(|(_, spellings)| spellings[0])
+    }
+
+    /// Reads a symbol such as "!?" or "±".
+    pub fn from_symbol(text: &str) -> Option<Nag> {
+        SYMBOLS
+            .iter()
+            .find(|(_, spellings)| spellings.contains(&text))
+            .map(|&(code, _)| Nag(code))
+    }
+}
+
+impl fmt::Display for Nag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.symbol() {
+            Some(symbol) => write!(f, "{}", symbol),
+            None => write!(f, "${}", self.0),
+        }
+    }
+}
+
+/// Colors for arrows and highlighted squares.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MarkColor {
+    Green,
+    Red,
+    Yellow,
+    Blue,
+}
+
+impl MarkColor {
+    /// The letter naming the color in PGN drawing commands.
+    pub fn letter(self) -> char {
+        match self {
+            MarkColor::Green => 'G',
+            MarkColor::Red => 'R',
+            MarkColor::Yellow => 'Y',
+            MarkColor::Blue => 'B',
+        }
+    }
+
+    pub fn from_letter(letter: char) -> Option<MarkColor> {
+        match letter {
+            'G' => Some(MarkColor::Green),
+            'R' => Some(MarkColor::Red),
+            'Y' => Some(MarkColor::Yellow),
+            'B' => Some(MarkColor::Blue),
+            _ => None,
+        }
+    }
+}
+
+/// An arrow drawn from one square to another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Arrow {
+    pub from_x: usize,
+    pub from_y: usize,
+    pub to_x: usize,
+    pub to_y: usize,
+    pub color: MarkColor,
+}
+
+/// A highlighted square.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Highlight {
+    pub x: usize,
+    pub y: usize,
+    pub color: MarkColor,
+}
+
+impl GameStateManager {
+    /// Replaces the annotation of the move leading to a node.
+    pub fn annotate_move(
+        &mut self,
+        node: NodeId,
+        annotation: Annotation,
+    ) -> Result<(), ChessError> {
+        if !self.tree.annotate(node, annotation) {
+            return Err(ChessError::UnknownNode);
+        }
+        // The node may lie on the line shown on the board
+        self.history = self.tree.history(&self.tree.path(self.current));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::moves::Move;
+
+    #[test]
+    fn test_nag_symbols() {
+        assert_eq!(Nag::INTERESTING.symbol(), Some("!?"));
+        assert_eq!(Nag::from_symbol("??"), Some(Nag::BLUNDER));
+        assert_eq!(Nag::from_symbol("+="), Some(Nag(14)));
+        assert_eq!(Nag(14).to_string(), "⩲");
+        assert_eq!(Nag(22).to_string(), "$22");
+        assert_eq!(Nag::from_symbol("!!!"), None);
+    }
+
+    #[test]
+    fn test_annotations_follow_the_move() {
+        let mut manager = GameStateManager::new();
+        for text in ["h2e2", "h9g7"] {
+            manager.play_move(Move::from_iccs(text).unwrap()).unwrap();
+        }
+        let annotation = Annotation {
+            comment: Some("Central cannon".to_string()),
+            nags: vec![Nag::GOOD],
+            arrows: vec![Arrow {
+                from_x: 4,
+                from_y: 7,
+                to_x: 4,
+                to_y: 3,
+                color: MarkColor::Red,
+            }],
+            highlights: vec![Highlight {
+                x: 4,
+                y: 3,
+                color: MarkColor::Yellow,
+            }],
+        };
+        manager.annotate_move(1, annotation.clone()).unwrap();
+        assert_eq!(manager.history.rounds[0].red_move.annotation, annotation);
+        assert_eq!(
+            manager.annotate_move(0, Annotation::default()),
+            Err(ChessError::UnknownNode)
+        );
+
+        // Taking the move back and playing it again keeps the annotation
+        manager.goto_first().unwrap();
+        manager.play_move(Move::from_iccs("h2e2").unwrap()).unwrap();
+        assert_eq!(manager.history.rounds[0].red_move.annotation, annotation);
+
+        let json = serde_json::to_value(&manager.history).unwrap();
+        let red_move = &json["rounds"][0]["red_move"]["annotation"];
+        assert_eq!(red_move["comment"], "Central cannon");
+        assert_eq!(red_move["nags"][0], 1);
+        assert_eq!(red_move["arrows"][0]["color"], "Red");
+    }
+}
@@ -0,0 +1,236 @@
+use crate::board::Board;
+use crate::movegen::{
+    first_piece, forward_dy, has_crossed_river, DIAGONAL, HORSE_JUMPS, ORTHOGONAL,
+};
+use crate::piece::{Color, Piece, PieceType};
+use crate::rules;
+use crate::square::Square;
+
+/// Number of pieces of one color attacking each square.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttackMap {
+    pub color: Color,
+    counts: [u8; Square::COUNT],
+}
+
+impl AttackMap {
+    /// Number of pieces attacking the square.
+    pub fn count(&self, square: Square) -> usize {
+        self.counts[square.index()] as usize
+    }
+
+    pub fn is_attacked(&self, square: Square) -> bool {
+        self.count(square) > 0
+    }
+
+    /// Iterates over the attacked squares.
+    pub fn squares(&self) -> impl Iterator<Item = Square> + '_ {
+        Square::all().filter(|&square| self.is_attacked(square))
+    }
+}
+
+/// Lists the pieces of `color` that could capture on `square`. Squares held by
+/// `color` itself count too, so the result doubles as the list of defenders.
+pub fn attackers_of(board: &Board, square: Square, color: Color) -> Vec<Square> {
+    let mut attackers = Vec::new();
+    visit_attackers(board, square, color, |attacker| {
+        attackers.push(attacker);
+        false
+    });
+    attackers
+}
+
+/// Returns true when any piece of color `by` could capture on `square`.
+pub fn is_square_attacked(board: &Board, square: Square, by: Color) -> bool {
+    visit_attackers(board, square, by, |_| true)
+}
+
+/// Builds the attack map of every square for the given color.
+pub fn attack_map(board: &Board, color: Color) -> AttackMap {
+    let mut counts = [0; Square::COUNT];
+    for square in Square::all() {
+        visit_attackers(board, square, color, |_| {
+            counts[square.index()] += 1;
+            false
+        });
+    }
+    AttackMap { color, counts }
+}
+
+/// Lists the pieces giving check to the general of the given color.
+pub fn checkers(board: &Board, color: Color) -> Vec<Square> {
+    match rules::find_general(board, color) {
+        Some(general) => attackers_of(board, general, color.opponent()),
+        None => Vec::new(),
+    }
+}
+
+// Calls `visit` with each attacker by looking outwards from the square for
+// pieces that could reach it. Stops and returns true as soon as `visit` does.
+fn visit_attackers(
+    board: &Board,
+    square: Square,
+    by: Color,
+    mut visit: impl FnMut(Square) -> bool,
+) -> bool {
+    let is_enemy =
+        |at: Square, piece_type: PieceType| board.piece_at(at) == Some(Piece::new(piece_type, by));
+    // The flying general only ever captures the other general
+    let target_is_general = matches!(
+        board.piece_at(square),
+        Some(piece) if piece.piece_type == PieceType::General
+    );
+
+    // Chariots, cannons and the flying general along ranks and files
+    for (dx, dy) in ORTHOGONAL {
+        if let Some(first) = first_piece(board, square, dx, dy) {
+            let piece = board.piece_at(first).unwrap();
+            if piece.color == by
+                && (piece.piece_type == PieceType::Chariot
+                    || (target_is_general && dx == 0 && piece.piece_type == PieceType::General))
+                && visit(first)
+            {
+                return true;
+            }
+            if let Some(second) = first_piece(board, first, dx, dy) {
+                if is_enemy(second, PieceType::Cannon) && visit(second) {
+                    return true;
+                }
+            }
+        }
+    }
+
+    // Horses, whose leg is the square next to the horse
+    for ((dx, dy), (leg_x, leg_y)) in HORSE_JUMPS {
+        if let Some(horse) = square.offset(-dx, -dy) {
+            if is_enemy(horse, PieceType::Horse) {
+                if let Some(leg) = horse.offset(leg_x, leg_y) {
+                    if board.piece_at(leg).is_none() && visit(horse) {
+                        return true;
+                    }
+                }
+            }
+        }
+    }
+
+    // Soldiers from behind, or from the side once across the river
+    if let Some(soldier) = square.offset(0, -forward_dy(by)) {
+        if is_enemy(soldier, PieceType::Soldier) && visit(soldier) {
+            return true;
+        }
+    }
+    for dx in [-1, 1] {
+        if let Some(soldier) = square.offset(dx, 0) {
+            if is_enemy(soldier, PieceType::Soldier)
+                && has_crossed_river(soldier.y(), by)
+                && visit(soldier)
+            {
+                return true;
+            }
+        }
+    }
+
+    // Generals, advisors and elephants only reach squares in their own territory
+    let (x, y) = square.to_xy();
+    if rules::is_legal_square(Piece::new(PieceType::General, by), x, y) {
+        for (dx, dy) in ORTHOGONAL {
+            if let Some(general) = square.offset(dx, dy) {
+                if is_enemy(general, PieceType::General) && visit(general) {
+                    return true;
+                }
+            }
+        }
+    }
+
+    if rules::is_legal_square(Piece::new(PieceType::Advisor, by), x, y) {
+        for (dx, dy) in DIAGONAL {
+            if let Some(advisor) = square.offset(dx, dy) {
+                if is_enemy(advisor, PieceType::Advisor) && visit(advisor) {
+                    return true;
+                }
+            }
+        }
+    }
+
+    if rules::is_legal_square(Piece::new(PieceType::Elephant, by), x, y) {
+        for (dx, dy) in DIAGONAL {
+            if let (Some(elephant), Some(eye)) =
+                (square.offset(dx * 2, dy * 2), square.offset(dx, dy))
+            {
+                if is_enemy(elephant, PieceType::Elephant)
+                    && board.piece_at(eye).is_none()
+                    && visit(elephant)
+                {
+                    return true;
+                }
+            }
+        }
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::GameState;
+    use crate::movegen;
+
+    fn sq(x: usize, y: usize) -> Square {
+        Square::from_xy(x, y).unwrap()
+    }
+
+    #[test]
+    fn test_attackers_of_general() {
+        let mut board = Board::new();
+        board.set_piece(4, 0, Some(Piece::new(PieceType::General, Color::Black)));
+        board.set_piece(3, 9, Some(Piece::new(PieceType::General, Color::Red)));
+        // Double check from a chariot on the rank and a horse
+        board.set_piece(0, 0, Some(Piece::new(PieceType::Chariot, Color::Red)));
+        board.set_piece(3, 2, Some(Piece::new(PieceType::Horse, Color::Red)));
+        // A cannon without a screen does not attack
+        board.set_piece(8, 0, Some(Piece::new(PieceType::Cannon, Color::Red)));
+
+        let mut attackers = attackers_of(&board, sq(4, 0), Color::Red);
+        attackers.sort();
+        assert_eq!(attackers, vec![sq(0, 0), sq(3, 2)]);
+        assert_eq!(checkers(&board, Color::Black), attackers);
+        assert!(checkers(&board, Color::Red).is_empty());
+
+        // Blocking the horse's leg removes it from the list
+        board.set_piece(3, 1, Some(Piece::new(PieceType::Advisor, Color::Black)));
+        assert_eq!(attackers_of(&board, sq(4, 0), Color::Red), vec![sq(0, 0)]);
+    }
+
+    #[test]
+    fn test_flying_general_only_attacks_the_general() {
+        let mut board = Board::new();
+        board.set_piece(4, 0, Some(Piece::new(PieceType::General, Color::Black)));
+        board.set_piece(4, 9, Some(Piece::new(PieceType::General, Color::Red)));
+
+        assert!(is_square_attacked(&board, sq(4, 0), Color::Red));
+        assert!(!is_square_attacked(&board, sq(4, 1), Color::Red));
+    }
+
+    #[test]
+    fn test_attack_map_matches_pseudo_legal_captures() {
+        // Every capture the generator produces must show up in the attack map
+        let board = GameState::new().board;
+        for color in [Color::Red, Color::Black] {
+            let map = attack_map(&board, color);
+            for mv in movegen::pseudo_legal_moves(&board, color) {
+                if board.piece_at(mv.to()).is_some() {
+                    assert!(map.is_attacked(mv.to()));
+                }
+            }
+        }
+
+        // Both red cannons attack the black horses, which the chariots defend
+        let red = attack_map(&board, Color::Red);
+        assert_eq!(red.count(sq(1, 0)), 1);
+        assert_eq!(red.count(sq(7, 0)), 1);
+        assert_eq!(attack_map(&board, Color::Black).count(sq(1, 0)), 1);
+        // The middle soldier is covered by nobody in the opening
+        assert!(!red.is_attacked(sq(4, 3)));
+    }
+}
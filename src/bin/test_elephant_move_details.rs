@@ -1,11 +1,12 @@
 use chinese_chess::game::GameStateManager;
+use chinese_chess::moves::Move;
 use chinese_chess::piece::{Color, PieceType};
 use chinese_chess::rules;
 
 fn main() {
     println!("=== 黑象移动详细测试 ===\n");
 
-    let manager = GameStateManager::new();
+    let mut manager = GameStateManager::new();
 
     // 检查黑象 (2, 0) 的所有可能移动
     let from_x = 2;
@@ -33,14 +34,8 @@ fn main() {
         println!("象眼位置: ({}, {}) 棋子: {:?}", eye_x, eye_y, eye_piece);
 
         // 验证移动
-        let result = rules::validate_move(
-            &manager.state.board,
-            from_x,
-            from_y,
-            to_x,
-            to_y,
-            Color::Black,
-        );
+        let result = Move::from_coords(from_x, from_y, to_x, to_y)
+            .and_then(|mv| rules::validate_move(&mut manager.state.board, mv, Color::Black));
 
         println!("验证结果: {:?}\n", result);
     }
@@ -1,15 +1,40 @@
+use crate::moves::Move;
 use crate::piece::Piece;
+use crate::square::Square;
+use crate::zobrist;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "BoardCells")]
 pub struct Board {
     pub cells: [[Option<Piece>; 9]; 10],
+    /// Zobrist hash of the piece placement, kept up to date by `set_piece`
+    #[serde(skip)]
+    hash: u64,
+}
+
+// Serialized form of a board; the hash is recomputed on deserialization
+#[derive(Deserialize)]
+struct BoardCells {
+    cells: [[Option<Piece>; 9]; 10],
+}
+
+impl From<BoardCells> for Board {
+    fn from(data: BoardCells) -> Self {
+        let mut board = Self {
+            cells: data.cells,
+            hash: 0,
+        };
+        board.hash = board.compute_hash();
+        board
+    }
 }
 
 impl Board {
     pub fn new() -> Self {
         Self {
             cells: [[None; 9]; 10],
+            hash: 0,
         }
     }
 
@@ -23,6 +48,12 @@ impl Board {
 
     pub fn set_piece(&mut self, x: usize, y: usize, piece: Option<Piece>) {
         if x < 9 && y < 10 {
+            if let Some(old) = self.cells[y][x] {
+                self.hash ^= zobrist::piece_key(old, x, y);
+            }
+            if let Some(new) = piece {
+                self.hash ^= zobrist::piece_key(new, x, y);
+            }
             self.cells[y][x] = piece;
         }
     }
@@ -40,6 +71,63 @@ impl Board {
         self.set_piece(from_x, from_y, None);
         captured
     }
+
+    pub fn piece_at(&self, square: Square) -> Option<Piece> {
+        self.cells[square.y()][square.x()]
+    }
+
+    pub fn set_piece_at(&mut self, square: Square, piece: Option<Piece>) {
+        self.set_piece(square.x(), square.y(), piece);
+    }
+
+    /// Makes a move and returns the token that takes it back. The hash is
+    /// updated incrementally.
+    pub fn make_move(&mut self, mv: Move) -> Undo {
+        let undo = Undo {
+            mv,
+            captured: self.piece_at(mv.to()),
+            hash: self.hash,
+        };
+        self.set_piece_at(mv.to(), self.piece_at(mv.from()));
+        self.set_piece_at(mv.from(), None);
+        undo
+    }
+
+    /// Takes back a move made with `make_move`, restoring the captured piece
+    /// and the previous hash.
+    pub fn unmake_move(&mut self, undo: Undo) {
+        let (from, to) = (undo.mv.from(), undo.mv.to());
+        self.cells[from.y()][from.x()] = self.cells[to.y()][to.x()];
+        self.cells[to.y()][to.x()] = undo.captured;
+        self.hash = undo.hash;
+    }
+
+    /// Zobrist hash of the piece placement.
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    /// Recomputes the placement hash from scratch.
+    pub fn compute_hash(&self) -> u64 {
+        let mut hash = 0;
+        for y in 0..10 {
+            for x in 0..9 {
+                if let Some(piece) = self.cells[y][x] {
+                    hash ^= zobrist::piece_key(piece, x, y);
+                }
+            }
+        }
+        hash
+    }
+}
+
+/// Everything needed to take back a move made with `Board::make_move`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Undo {
+    pub mv: Move,
+    pub captured: Option<Piece>,
+    /// Hash of the board before the move
+    pub hash: u64,
 }
 
 impl Default for Board {
@@ -47,3 +135,79 @@ impl Default for Board {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::piece::{Color, PieceType};
+
+    #[test]
+    fn test_hash_is_updated_incrementally() {
+        let mut board = Board::new();
+        assert_eq!(board.hash(), 0);
+
+        let chariot = Piece::new(PieceType::Chariot, Color::Red);
+        let horse = Piece::new(PieceType::Horse, Color::Black);
+        board.set_piece(0, 9, Some(chariot));
+        board.set_piece(0, 3, Some(horse));
+        assert_eq!(board.hash(), board.compute_hash());
+
+        // Capture, then restore the captured piece
+        let before = board.hash();
+        let captured = board.move_piece(0, 9, 0, 3);
+        assert_eq!(captured, Some(horse));
+        assert_eq!(board.hash(), board.compute_hash());
+        assert_ne!(board.hash(), before);
+
+        board.move_piece(0, 3, 0, 9);
+        board.set_piece(0, 3, captured);
+        assert_eq!(board.hash(), before);
+
+        // Overwriting a square replaces the old piece's key
+        board.set_piece(0, 3, Some(chariot));
+        assert_eq!(board.hash(), board.compute_hash());
+        board.set_piece(0, 3, None);
+        board.set_piece(0, 9, None);
+        assert_eq!(board.hash(), 0);
+    }
+
+    #[test]
+    fn test_make_and_unmake_move() {
+        let mut board = Board::new();
+        let chariot = Piece::new(PieceType::Chariot, Color::Red);
+        let horse = Piece::new(PieceType::Horse, Color::Black);
+        board.set_piece(0, 9, Some(chariot));
+        board.set_piece(0, 3, Some(horse));
+        let before = board.clone();
+
+        // Capture
+        let undo = board.make_move(Move::from_coords(0, 9, 0, 3).unwrap());
+        assert_eq!(undo.captured, Some(horse));
+        assert_eq!(undo.hash, before.hash());
+        assert_eq!(board.get_piece(0, 3), Some(chariot));
+        assert_eq!(board.hash(), board.compute_hash());
+        board.unmake_move(undo);
+        assert_eq!(board, before);
+        assert_eq!(board.hash(), before.hash());
+
+        // Quiet move onto an empty square
+        let undo = board.make_move(Move::from_coords(0, 9, 5, 9).unwrap());
+        assert_eq!(undo.captured, None);
+        board.unmake_move(undo);
+        assert_eq!(board, before);
+    }
+
+    #[test]
+    fn test_hash_survives_serialization() {
+        let mut board = Board::new();
+        board.set_piece(4, 0, Some(Piece::new(PieceType::General, Color::Black)));
+        board.set_piece(4, 9, Some(Piece::new(PieceType::General, Color::Red)));
+
+        let json = serde_json::to_string(&board).unwrap();
+        assert!(!json.contains("hash"));
+
+        let restored: Board = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.hash(), board.hash());
+        assert_eq!(restored, board);
+    }
+}
@@ -1,9 +1,24 @@
 use chinese_chess::game::GameStateManager;
+use chinese_chess::moves::Move;
+use chinese_chess::notation::MoveFormat;
+use chinese_chess::pgn::PgnGame;
 use clap::{Parser, Subcommand};
 use colored::*;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+// Tag of the saved game holding how many of its moves are on the board; the
+// moves after it were taken back and can be redone
+const CURRENT_PLY: &str = "CurrentPly";
 
 #[derive(Parser)]
 struct Cli {
+    /// PGN file the game is read from and saved to, so moves, undo and redo
+    /// carry over from one run to the next
+    #[arg(long, global = true)]
+    game: Option<PathBuf>,
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -12,15 +27,36 @@ struct Cli {
 enum Commands {
     /// Start a new game
     New,
-    /// Make a move (format: from_x,from_y to_x,to_y)
-    Move { from: String, to: String },
+    /// Make a move (format: from_x,from_y to_x,to_y, or ICCS such as h2e2)
+    Move { from: String, to: Option<String> },
     /// Undo last move
     Undo,
+    /// Redo the last undone move
+    Redo,
+    /// Count move tree leaf nodes from the position on the board
+    Perft {
+        depth: usize,
+        /// Break the count down by root move
+        #[arg(long)]
+        divide: bool,
+    },
 }
 
 fn main() {
-    let cli = Cli::parse();
-    let mut manager = GameStateManager::new();
+    run(&Cli::parse());
+}
+
+fn run(cli: &Cli) {
+    let mut manager = match &cli.game {
+        Some(path) if path.exists() => match load_game(path) {
+            Ok(manager) => manager,
+            Err(e) => {
+                println!("{}: {}", "Error".red(), e);
+                return;
+            }
+        },
+        _ => GameStateManager::new(),
+    };
 
     match &cli.command {
         Some(Commands::New) => {
@@ -33,8 +69,8 @@ fn main() {
             );
         }
         Some(Commands::Move { from, to }) => {
-            if let (Some(from_coord), Some(to_coord)) = parse_coordinates(from, to) {
-                match manager.make_move(from_coord.0, from_coord.1, to_coord.0, to_coord.1) {
+            if let Some(mv) = parse_move(from, to.as_deref()) {
+                match manager.play_move(mv) {
                     Ok(_) => {
                         println!("{}", "Move successful!".green());
                         println!(
@@ -45,11 +81,12 @@ fn main() {
                         if manager.state.is_in_check {
                             println!("{}", "Check!".yellow());
                         }
-                        if manager.state.is_ended {
+                        if let Some(result) = manager.state.result {
                             println!(
-                                "{}: {}",
+                                "{}: {} ({:?})",
                                 "Game Over! Winner".blue(),
-                                format!("{:?}", manager.state.winner).red()
+                                format!("{:?}", result.winner).red(),
+                                result.termination
                             );
                         }
                     }
@@ -58,7 +95,10 @@ fn main() {
                     }
                 }
             } else {
-                println!("{}", "Invalid coordinate format! Use x,y format.".red());
+                println!(
+                    "{}",
+                    "Invalid move format! Use x,y x,y or ICCS such as h2e2.".red()
+                );
             }
         }
         Some(Commands::Undo) => match manager.undo_move() {
@@ -74,15 +114,83 @@ fn main() {
                 println!("{}: {:?}", "Error".red(), e);
             }
         },
+        Some(Commands::Redo) => match manager.redo_move() {
+            Ok(_) => {
+                println!("{}", "Redo successful!".green());
+                println!(
+                    "{}: {}",
+                    "Current Turn".blue(),
+                    format!("{:?}", manager.state.current_turn).red()
+                );
+            }
+            Err(e) => {
+                println!("{}: {:?}", "Error".red(), e);
+            }
+        },
+        Some(Commands::Perft { depth, divide }) => {
+            let start = Instant::now();
+            let nodes = if *divide {
+                let counts = manager.state.divide(*depth);
+                for (mv, count) in &counts {
+                    println!("{}: {}", mv.to_iccs(), count);
+                }
+                counts.iter().map(|&(_, count)| count).sum()
+            } else {
+                manager.state.perft(*depth)
+            };
+            println!(
+                "{}: {} ({:.3}s)",
+                format!("Perft({})", depth).blue(),
+                nodes.to_string().green(),
+                start.elapsed().as_secs_f64()
+            );
+        }
         None => {
             println!("{}", "Chinese Chess (Xiangqi)".bold().blue());
             println!("{}", "Use --help for available commands.".yellow());
         }
     }
+
+    if let Some(path) = &cli.game {
+        if let Err(e) = save_game(&manager, path) {
+            println!("{}: {}", "Error".red(), e);
+        }
+    }
+}
+
+/// Reads a game saved by `save_game`, taking back the moves after the
+/// current ply so that they can be redone.
+fn load_game(path: &Path) -> Result<GameStateManager, Box<dyn Error>> {
+    let game: PgnGame = fs::read_to_string(path)?.parse()?;
+    let mut manager = game.to_manager()?;
+    if let Some(ply) = game.tag(CURRENT_PLY) {
+        manager.goto_ply(ply.parse()?)?;
+    }
+    Ok(manager)
+}
+
+/// Saves the current line as PGN, the moves that can be redone included.
+fn save_game(manager: &GameStateManager, path: &Path) -> Result<(), Box<dyn Error>> {
+    let mut line = manager.clone();
+    line.goto_last()?;
+    let mut game = PgnGame::from_manager(&line, MoveFormat::Iccs);
+    game.set_tag(CURRENT_PLY, &manager.current_ply().to_string());
+    fs::write(path, game.to_string())?;
+    Ok(())
 }
 
 type Coordinate = (usize, usize);
 
+fn parse_move(from: &str, to: Option<&str>) -> Option<Move> {
+    match to {
+        Some(to) => match parse_coordinates(from, to) {
+            (Some(from), Some(to)) => Move::from_coords(from.0, from.1, to.0, to.1).ok(),
+            _ => None,
+        },
+        None => Move::from_iccs(from).ok(),
+    }
+}
+
 fn parse_coordinates(from: &str, to: &str) -> (Option<Coordinate>, Option<Coordinate>) {
     let parse = |s: &str| {
         let parts: Vec<&str> = s.split(',').collect();
@@ -97,3 +205,61 @@ fn parse_coordinates(from: &str, to: &str) -> (Option<Coordinate>, Option<Coordi
 
     (parse(from), parse(to))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_with(path: &Path, args: &[&str]) {
+        let mut argv = vec!["chinese-chess-cli", "--game", path.to_str().unwrap()];
+        argv.extend_from_slice(args);
+        run(&Cli::parse_from(argv));
+    }
+
+    #[test]
+    fn test_undo_and_redo_across_runs() {
+        let path = std::env::temp_dir().join(format!("cli-redo-{}.pgn", std::process::id()));
+        run_with(&path, &["new"]);
+        run_with(&path, &["move", "h2e2"]);
+        run_with(&path, &["move", "7,0", "6,2"]);
+        let played = load_game(&path).unwrap();
+
+        // Both moves are taken back but kept in the file,
+        run_with(&path, &["undo"]);
+        run_with(&path, &["undo"]);
+        let undone = load_game(&path).unwrap();
+        assert_eq!(undone.current_ply(), 0);
+        assert_eq!(undone.state, GameStateManager::new().state);
+
+        // and come back exactly as they were played
+        run_with(&path, &["redo"]);
+        run_with(&path, &["redo"]);
+        let redone = load_game(&path).unwrap();
+        assert_eq!(redone.history, played.history);
+        assert_eq!(redone.state, played.state);
+
+        // Nothing is left to redo
+        run_with(&path, &["redo"]);
+        assert_eq!(load_game(&path).unwrap().history, played.history);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_game_file_going_on_after_a_repetition() {
+        // A recorded game whose players went on past a threefold repetition
+        let path = std::env::temp_dir().join(format!("cli-repeat-{}.pgn", std::process::id()));
+        fs::write(
+            &path,
+            "1. h0g2 h9g7 2. g2h0 g7h9 3. h0g2 h9g7 4. g2h0 g7h9 5. h2e2 *\n",
+        )
+        .unwrap();
+        run_with(&path, &["undo"]);
+        run_with(&path, &["redo"]);
+        run_with(&path, &["move", "h7e7"]);
+
+        let manager = load_game(&path).unwrap();
+        assert_eq!(manager.history.moves().count(), 10);
+        assert!(!manager.state.is_ended);
+        fs::remove_file(&path).unwrap();
+    }
+}
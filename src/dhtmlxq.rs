@@ -0,0 +1,699 @@
+use crate::annotation::Annotation;
+use crate::board::Board;
+use crate::game::{GameState, GameStateManager};
+use crate::history::MoveRecord;
+use crate::moves::Move;
+use crate::piece::{Color, Piece, PieceType};
+use crate::rules;
+use crate::square::Square;
+use crate::tree::ROOT;
+use crate::ChessError;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+// DhtmlXQ is the format of the DhtmlXQ web viewer, pasted into forum posts
+// as UBB style tags such as [DhtmlXQ_red]...[/DhtmlXQ_red]. A square is two
+// digits, the file and the rank counted from Black's top left, so a move is
+// four digits: 7747 is h2e2. binit lists the squares of the 32 pieces, 99
+// for captured ones. A variation of line P whose first move is move S of the
+// game is stored as move_P_S_B, B being its own line number and the main
+// line being 0. commentS and commentB_S hold the comment after move S of
+// the main line or of variation B, comment0 the one before the first move.
+
+/// Piece order of the 32 squares in binit, Red first.
+const PIECE_ORDER: [PieceType; 16] = [
+    PieceType::Chariot,
+    PieceType::Horse,
+    PieceType::Elephant,
+    PieceType::Advisor,
+    PieceType::General,
+    PieceType::Advisor,
+    PieceType::Elephant,
+    PieceType::Horse,
+    PieceType::Chariot,
+    PieceType::Cannon,
+    PieceType::Cannon,
+    PieceType::Soldier,
+    PieceType::Soldier,
+    PieceType::Soldier,
+    PieceType::Soldier,
+    PieceType::Soldier,
+];
+
+const OFF_BOARD: &str = "99";
+
+/// A game read from DhtmlXQ tags.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DhtmlXqGame {
+    /// Game information such as title, event, date, place, red, black and
+    /// result, named without the DhtmlXQ_ prefix, in file order
+    pub tags: Vec<(String, String)>,
+    /// Position before the first move
+    pub start: GameState,
+    /// The move tree; node 0 is the start position and has no move
+    pub nodes: Vec<DhtmlXqNode>,
+}
+
+/// A node of the move tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DhtmlXqNode {
+    /// The move leading to this node
+    pub mv: Option<Move>,
+    pub comment: String,
+    pub parent: Option<usize>,
+    /// Continuations, the main line first
+    pub children: Vec<usize>,
+}
+
+impl DhtmlXqGame {
+    /// Records a played game from its start position with every variation
+    /// in its game tree, with the result as the only tag.
+    pub fn from_manager(manager: &GameStateManager) -> Self {
+        let start = &manager.positions[0];
+        let tree = &manager.tree;
+        let mut game = Self {
+            tags: Vec::new(),
+            start: GameState::from_board(start.board.clone(), start.side_to_move),
+            nodes: (0..tree.node_count())
+                .filter_map(|node| tree.node(node))
+                .map(|node| DhtmlXqNode {
+                    mv: node.record.as_ref().map(MoveRecord::mv),
+                    // The root holds the comment before the first move
+                    comment: node
+                        .record
+                        .as_ref()
+                        .map_or(tree.comment(), |record| {
+                            record.annotation.comment.as_deref()
+                        })
+                        .unwrap_or_default()
+                        .to_string(),
+                    parent: node.parent,
+                    children: node.children.clone(),
+                })
+                .collect(),
+        };
+        game.set_tag("result", result_text(&manager.state));
+        game
+    }
+
+    /// The moves of the main line, following the first child at every node.
+    pub fn main_line(&self) -> Vec<Move> {
+        let mut moves = Vec::new();
+        let mut node = &self.nodes[0];
+        while let Some(&child) = node.children.first() {
+            node = &self.nodes[child];
+            moves.extend(node.mv);
+        }
+        moves
+    }
+
+    /// Replays the moves from the start position into the game tree,
+    /// variations included, ending at the end of the main line. Positions
+    /// are only judged at the end, so lines that went on past a repetition
+    /// still load. A decisive result that the moves do not reach on the
+    /// board is applied as a resignation, a drawn one as an agreed draw.
+    /// Comments are kept as move annotations, the one before the first move
+    /// on the game tree.
+    pub fn to_manager(&self) -> Result<GameStateManager, ChessError> {
+        let mut manager = GameStateManager::from_state(self.start.clone());
+        manager.update_status();
+        manager
+            .tree
+            .set_comment(Some(self.nodes[0].comment.clone()));
+
+        // Parents always come before their children, and the main line
+        // before its variations
+        let mut tree_nodes = vec![ROOT; self.nodes.len()];
+        for (index, node) in self.nodes.iter().enumerate().skip(1) {
+            manager.goto_node(tree_nodes[node.parent.unwrap()])?;
+            manager.replay_move(node.mv.unwrap())?;
+            tree_nodes[index] = manager.current_node();
+            if !node.comment.is_empty() {
+                let annotation = Annotation {
+                    comment: Some(node.comment.clone()),
+                    ..Annotation::default()
+                };
+                manager.annotate_move(tree_nodes[index], annotation)?;
+            }
+        }
+        let end = manager.tree.main_line().last().copied().unwrap_or(ROOT);
+        manager.goto_node(end)?;
+
+        if !manager.state.is_ended {
+            manager.update_status();
+        }
+        if !manager.state.is_ended {
+            match self.tag("result") {
+                Some("红胜") => manager.resign(Color::Black)?,
+                Some("黑胜") => manager.resign(Color::Red)?,
+                Some("和棋") => manager.agree_draw()?,
+                _ => {}
+            }
+        }
+        Ok(manager)
+    }
+
+    /// Returns the value of a tag, named without the DhtmlXQ_ prefix.
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Sets a tag, replacing its value if it is already present.
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(tag, _)| tag == name) {
+            Some((_, old)) => *old = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    // Splits the tree into lines for writing: the main line and then every
+    // variation as (parent line, first move number, nodes)
+    fn lines(&self) -> Vec<(usize, usize, Vec<usize>)> {
+        let follow = |mut node: usize| {
+            let mut line = vec![node];
+            while let Some(&child) = self.nodes[node].children.first() {
+                line.push(child);
+                node = child;
+            }
+            line
+        };
+
+        let main_line = follow(0)[1..].to_vec();
+        let mut lines = vec![(0, 1, main_line)];
+        let mut index = 0;
+        while index < lines.len() {
+            let (_, first, line) = lines[index].clone();
+            for (ply, &node) in (first..).zip(&line) {
+                let parent = self.nodes[node].parent.unwrap();
+                // Each alternative is listed by the line of its first sibling
+                if self.nodes[parent].children.first() == Some(&node) {
+                    for &sibling in &self.nodes[parent].children[1..] {
+                        lines.push((index, ply, follow(sibling)));
+                    }
+                }
+            }
+            index += 1;
+        }
+        lines
+    }
+}
+
+impl DhtmlXqNode {
+    fn new(mv: Option<Move>, parent: Option<usize>) -> Self {
+        Self {
+            mv,
+            comment: String::new(),
+            parent,
+            children: Vec::new(),
+        }
+    }
+}
+
+/// Parses the DhtmlXQ tags in a text, ignoring anything around them.
+pub fn parse(text: &str) -> Result<DhtmlXqGame, ChessError> {
+    let tags = read_tags(text)?;
+    if tags.is_empty() {
+        return Err(invalid("no DhtmlXQ tags found".to_string()));
+    }
+
+    let mut info = Vec::new();
+    let mut board = None;
+    let mut main_line = String::new();
+    let mut variations = Vec::new();
+    let mut comments = Vec::new();
+    for (name, value) in tags {
+        if name == "binit" {
+            board = Some(decode_board(&value)?);
+        } else if name == "movelist" {
+            main_line = value;
+        } else if let Some(numbers) = name.strip_prefix("move_") {
+            match numbers_of(numbers)[..] {
+                [parent, first, line] if first > 0 && line > 0 => {
+                    variations.push((parent, first, line, value))
+                }
+                _ => return Err(invalid(format!("malformed tag '{}'", name))),
+            }
+        } else if let Some(numbers) = name.strip_prefix("comment") {
+            match numbers_of(numbers)[..] {
+                [ply] => comments.push((0, ply, value)),
+                [line, ply] => comments.push((line, ply, value)),
+                _ => return Err(invalid(format!("malformed tag '{}'", name))),
+            }
+        } else {
+            info.push((name, value));
+        }
+    }
+
+    let board = board.unwrap_or_else(|| GameState::new().board);
+    let mut tree = TreeBuilder::new(board);
+
+    // Nodes of every line from the start position, indexed by move number
+    let mut paths = HashMap::from([(0, vec![0])]);
+    for mv in decode_moves(&main_line)? {
+        let path = paths.get_mut(&0).unwrap();
+        path.push(tree.add(*path.last().unwrap(), mv)?);
+    }
+
+    // Variations may come before the line they branch from
+    while !variations.is_empty() {
+        let ready = variations
+            .iter()
+            .position(|(parent, ..)| paths.contains_key(parent))
+            .ok_or_else(|| {
+                invalid(format!(
+                    "variation {} branches from a missing line",
+                    variations[0].2
+                ))
+            })?;
+        let (parent, first, line, moves) = variations.remove(ready);
+        let mut path = paths[&parent].clone();
+        if first > path.len() {
+            return Err(invalid(format!(
+                "variation {} starts after the end of line {}",
+                line, parent
+            )));
+        }
+        path.truncate(first);
+        for mv in decode_moves(&moves)? {
+            path.push(tree.add(*path.last().unwrap(), mv)?);
+        }
+        paths.insert(line, path);
+    }
+
+    for (line, ply, text) in comments {
+        if let Some(&node) = paths.get(&line).and_then(|path| path.get(ply)) {
+            tree.nodes[node].comment = text.replace("||", "\n");
+        }
+    }
+
+    let side = tree.sides[0].unwrap_or(Color::Red);
+    Ok(DhtmlXqGame {
+        tags: info,
+        start: GameState::from_position(tree.boards.swap_remove(0), side)?,
+        nodes: tree.nodes,
+    })
+}
+
+impl fmt::Display for DhtmlXqGame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let write_tag = |f: &mut fmt::Formatter<'_>, name: &str, value: &str| {
+            writeln!(f, "[DhtmlXQ_{}]{}[/DhtmlXQ_{}]", name, value, name)
+        };
+
+        writeln!(f, "[DhtmlXQ]")?;
+        for (name, value) in &self.tags {
+            write_tag(f, name, value)?;
+        }
+        if self.start.board != GameState::new().board {
+            write_tag(f, "binit", &encode_board(&self.start.board))?;
+        }
+
+        let lines = self.lines();
+        for (line, &(parent, first, ref nodes)) in lines.iter().enumerate() {
+            let moves: String = nodes
+                .iter()
+                .filter_map(|&node| self.nodes[node].mv)
+                .map(|mv| encode_square(mv.from()) + &encode_square(mv.to()))
+                .collect();
+            if line == 0 {
+                write_tag(f, "movelist", &moves)?;
+            } else {
+                write_tag(f, &format!("move_{}_{}_{}", parent, first, line), &moves)?;
+            }
+        }
+
+        if !self.nodes[0].comment.is_empty() {
+            write_tag(f, "comment0", &self.nodes[0].comment.replace('\n', "||"))?;
+        }
+        for (line, (_, first, nodes)) in lines.iter().enumerate() {
+            for (ply, &node) in (*first..).zip(nodes) {
+                let comment = &self.nodes[node].comment;
+                if comment.is_empty() {
+                    continue;
+                }
+                let name = match line {
+                    0 => format!("comment{}", ply),
+                    _ => format!("comment{}_{}", line, ply),
+                };
+                write_tag(f, &name, &comment.replace('\n', "||"))?;
+            }
+        }
+        writeln!(f, "[/DhtmlXQ]")
+    }
+}
+
+impl FromStr for DhtmlXqGame {
+    type Err = ChessError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        parse(text)
+    }
+}
+
+// Builds the move tree, checking every move against the position it is
+// played in and the side to move there
+struct TreeBuilder {
+    nodes: Vec<DhtmlXqNode>,
+    boards: Vec<Board>,
+    // The root's side is set by the first move
+    sides: Vec<Option<Color>>,
+}
+
+impl TreeBuilder {
+    fn new(start: Board) -> Self {
+        Self {
+            nodes: vec![DhtmlXqNode::new(None, None)],
+            boards: vec![start],
+            sides: vec![None],
+        }
+    }
+
+    fn add(&mut self, parent: usize, mv: Move) -> Result<usize, ChessError> {
+        let mut board = self.boards[parent].clone();
+        let color = match board.piece_at(mv.from()) {
+            Some(piece) => piece.color,
+            None => {
+                return Err(invalid(format!(
+                    "move {} from an empty square",
+                    mv.to_iccs()
+                )))
+            }
+        };
+        if *self.sides[parent].get_or_insert(color) != color
+            || rules::validate_legal_move(&mut board, mv, color).is_err()
+        {
+            return Err(invalid(format!("illegal move {}", mv.to_iccs())));
+        }
+        board.make_move(mv);
+
+        let node = self.nodes.len();
+        self.nodes.push(DhtmlXqNode::new(Some(mv), Some(parent)));
+        self.nodes[parent].children.push(node);
+        self.boards.push(board);
+        self.sides.push(Some(color.opponent()));
+        Ok(node)
+    }
+}
+
+// Reads every [DhtmlXQ_name]value[/DhtmlXQ_name] pair in order
+fn read_tags(text: &str) -> Result<Vec<(String, String)>, ChessError> {
+    const OPEN: &str = "[DhtmlXQ_";
+    let mut tags = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find(OPEN) {
+        let after = &rest[start + OPEN.len()..];
+        let name = after
+            .find(']')
+            .map(|end| &after[..end])
+            .filter(|name| !name.is_empty() && !name.contains(['[', ' ', '\n']))
+            .ok_or_else(|| invalid("malformed tag".to_string()))?;
+        let body = &after[name.len() + 1..];
+        let close = format!("[/DhtmlXQ_{}]", name);
+        let end = body
+            .find(&close)
+            .ok_or_else(|| invalid(format!("unterminated tag '{}'", name)))?;
+        tags.push((name.to_string(), body[..end].trim().to_string()));
+        rest = &body[end + close.len()..];
+    }
+    Ok(tags)
+}
+
+// Numbers separated by underscores, empty when any of them is not a number
+fn numbers_of(text: &str) -> Vec<usize> {
+    text.split('_')
+        .map(|number| number.parse().ok())
+        .collect::<Option<_>>()
+        .unwrap_or_default()
+}
+
+fn decode_board(binit: &str) -> Result<Board, ChessError> {
+    let digits: Vec<char> = binit.chars().filter(|c| !c.is_whitespace()).collect();
+    if digits.len() != 64 {
+        return Err(invalid("binit must list 32 squares".to_string()));
+    }
+
+    let mut board = Board::new();
+    for (i, pair) in digits.chunks(2).enumerate() {
+        let text: String = pair.iter().collect();
+        if text == OFF_BOARD {
+            continue;
+        }
+        let square = decode_square(&text)
+            .ok_or_else(|| invalid(format!("malformed square '{}' in binit", text)))?;
+        if board.piece_at(square).is_some() {
+            return Err(invalid(format!("two pieces on square {}", text)));
+        }
+        let color = if i < 16 { Color::Red } else { Color::Black };
+        board.set_piece_at(square, Some(Piece::new(PIECE_ORDER[i % 16], color)));
+    }
+    Ok(board)
+}
+
+fn encode_board(board: &Board) -> String {
+    let mut squares = vec![OFF_BOARD.to_string(); 32];
+    for color in [Color::Red, Color::Black] {
+        let offset = if color == Color::Red { 0 } else { 16 };
+        // Red is listed from its right, Black from its left, as viewers do
+        let files: Vec<usize> = match color {
+            Color::Red => (0..9).rev().collect(),
+            Color::Black => (0..9).collect(),
+        };
+        for x in files {
+            for y in 0..10 {
+                let Some(piece) = board.get_piece(x, y).filter(|piece| piece.color == color) else {
+                    continue;
+                };
+                let slot = (0..16).find(|&slot| {
+                    PIECE_ORDER[slot] == piece.piece_type && squares[offset + slot] == OFF_BOARD
+                });
+                if let Some(slot) = slot {
+                    squares[offset + slot] = format!("{}{}", x, y);
+                }
+            }
+        }
+    }
+    squares.concat()
+}
+
+fn decode_moves(text: &str) -> Result<Vec<Move>, ChessError> {
+    let digits: Vec<char> = text.chars().filter(|c| !c.is_whitespace()).collect();
+    if !digits.len().is_multiple_of(4) {
+        return Err(invalid(format!("malformed move list '{}'", text)));
+    }
+    digits
+        .chunks(4)
+        .map(|chunk| {
+            let text: String = chunk.iter().collect();
+            let from = decode_square(&text[..2]);
+            let to = decode_square(&text[2..]);
+            from.zip(to)
+                .map(|(from, to)| Move::new(from, to))
+                .ok_or_else(|| invalid(format!("malformed move '{}'", text)))
+        })
+        .collect()
+}
+
+// Squares are the file then the rank, counted from Black's top left
+fn decode_square(text: &str) -> Option<Square> {
+    let mut digits = text.chars().map(|c| c.to_digit(10));
+    let x = digits.next()??;
+    let y = digits.next()??;
+    Square::from_xy(x as usize, y as usize)
+}
+
+fn encode_square(square: Square) -> String {
+    format!("{}{}", square.x(), square.y())
+}
+
+fn result_text(state: &GameState) -> &'static str {
+    match &state.result {
+        Some(result) => match result.winner {
+            Some(Color::Red) => "红胜",
+            Some(Color::Black) => "黑胜",
+            None => "和棋",
+        },
+        None => "未知",
+    }
+}
+
+fn invalid(reason: String) -> ChessError {
+    ChessError::InvalidDhtmlXq(reason)
+}
+
+impl GameStateManager {
+    /// Starts a game from the main line of a DhtmlXQ block.
+    pub fn from_dhtmlxq(text: &str) -> Result<Self, ChessError> {
+        parse(text)?.to_manager()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Termination;
+
+    // Central cannon answered by the screen horse, or by a central cannon in
+    // a variation that has a variation of its own for Red's reply
+    const FORUM_POST: &str = "\
+        Nice game from the weekend!\n\
+        [DhtmlXQ]\n\
+        [DhtmlXQ_title]中炮对屏风马[/DhtmlXQ_title]\n\
+        [DhtmlXQ_red]Liu[/DhtmlXQ_red]\n\
+        [DhtmlXQ_result]红胜[/DhtmlXQ_result]\n\
+        [DhtmlXQ_movelist]774770627967[/DhtmlXQ_movelist]\n\
+        [DhtmlXQ_move_0_2_1]12427967[/DhtmlXQ_move_0_2_1]\n\
+        [DhtmlXQ_move_1_3_2]19271022[/DhtmlXQ_move_1_3_2]\n\
+        [DhtmlXQ_comment0]Opening||Two lines[/DhtmlXQ_comment0]\n\
+        [DhtmlXQ_comment2]Screen horse[/DhtmlXQ_comment2]\n\
+        [DhtmlXQ_comment2_4]好棋[/DhtmlXQ_comment2_4]\n\
+        [/DhtmlXQ]\n";
+
+    fn iccs(moves: Vec<Move>) -> Vec<String> {
+        moves.into_iter().map(Move::to_iccs).collect()
+    }
+
+    #[test]
+    fn test_parse_forum_post() {
+        let game = parse(FORUM_POST).unwrap();
+        assert_eq!(game.tag("title"), Some("中炮对屏风马"));
+        assert_eq!(game.tag("red"), Some("Liu"));
+        assert_eq!(game.start, GameState::new());
+        assert_eq!(iccs(game.main_line()), ["h2e2", "h9g7", "h0g2"]);
+
+        // h2e2 -> h9g7 -> h0g2, b7e7 -> h0g2 or b0c2 -> b9c7
+        assert_eq!(game.nodes.len(), 8);
+        assert_eq!(game.nodes[0].comment, "Opening\nTwo lines");
+        assert_eq!(game.nodes[1].children, [2, 4]);
+        assert_eq!(game.nodes[2].comment, "Screen horse");
+        assert_eq!(game.nodes[4].mv, Some(Move::from_iccs("b7e7").unwrap()));
+        assert_eq!(game.nodes[4].children, [5, 6]);
+        assert_eq!(game.nodes[6].mv, Some(Move::from_iccs("b0c2").unwrap()));
+        assert_eq!(game.nodes[7].comment, "好棋");
+        assert_eq!(game.nodes[7].parent, Some(6));
+
+        let manager = GameStateManager::from_dhtmlxq(FORUM_POST).unwrap();
+        assert_eq!(manager.chinese_moves(), ["炮二平五", "马8进7", "马二进三"]);
+        assert_eq!(manager.tree.node_count(), 8);
+        assert_eq!(manager.tree.children(4), [5, 6]);
+        assert_eq!(manager.state.winner, Some(Color::Red));
+        assert_eq!(
+            manager.state.result.map(|result| result.termination),
+            Some(Termination::Resignation)
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let game = parse(FORUM_POST).unwrap();
+        let text = game.to_string();
+        assert!(text.starts_with("[DhtmlXQ]\n[DhtmlXQ_title]"));
+        assert!(text.contains("[DhtmlXQ_move_1_3_2]19271022[/DhtmlXQ_move_1_3_2]\n"));
+        assert!(text.contains("[DhtmlXQ_comment0]Opening||Two lines[/DhtmlXQ_comment0]\n"));
+        assert_eq!(parse(&text).unwrap(), game);
+
+        // The variations survive a trip through the game tree, and so do
+        // the comments, the one before the first move included
+        let exported = DhtmlXqGame::from_manager(&game.to_manager().unwrap());
+        assert_eq!(exported.nodes, game.nodes);
+    }
+
+    #[test]
+    fn test_export_history() {
+        let mut manager = GameStateManager::new();
+        for text in ["h2e2", "h9g7", "h0g2", "i9h9"] {
+            manager.play_move(Move::from_iccs(text).unwrap()).unwrap();
+        }
+        assert_eq!(
+            DhtmlXqGame::from_manager(&manager).to_string(),
+            "[DhtmlXQ]\n[DhtmlXQ_result]未知[/DhtmlXQ_result]\n\
+             [DhtmlXQ_movelist]7747706279678070[/DhtmlXQ_movelist]\n[/DhtmlXQ]\n"
+        );
+
+        let reloaded =
+            GameStateManager::from_dhtmlxq(&DhtmlXqGame::from_manager(&manager).to_string())
+                .unwrap();
+        assert_eq!(reloaded.history, manager.history);
+        assert_eq!(reloaded.state, manager.state);
+    }
+
+    #[test]
+    fn test_endgame_with_black_to_move() {
+        let mut manager =
+            GameStateManager::from_fen("4k4/9/9/9/9/9/9/9/4A4/3K1C3 b - - 0 1").unwrap();
+        manager.play_move(Move::from_iccs("e9e8").unwrap()).unwrap();
+        let text = DhtmlXqGame::from_manager(&manager).to_string();
+        assert!(text.contains(
+            "[DhtmlXQ_binit]99999948399999999959999999999999\
+             99999999409999999999999999999999[/DhtmlXQ_binit]"
+        ));
+
+        let game = parse(&text).unwrap();
+        assert_eq!(game.start.current_turn, Color::Black);
+        assert_eq!(game.start.to_fen(), "4k4/9/9/9/9/9/9/9/4A4/3K1C3 b - - 0 1");
+        assert_eq!(game.to_manager().unwrap().state, manager.state);
+
+        // The standard position written out square by square
+        let standard = "[DhtmlXQ_binit]8979695949392919097717866646260600102030405060708012720323436383[/DhtmlXQ_binit]";
+        assert_eq!(parse(standard).unwrap().start, GameState::new());
+        assert_eq!(encode_board(&GameState::new().board), &standard[15..79]);
+    }
+
+    #[test]
+    fn test_lines_going_on_after_a_repetition() {
+        // The horses go out and back twice, bringing the start position up a
+        // third time; the main line then plays the central cannon and a
+        // variation the other cannon, with a reply
+        let mut manager = GameStateManager::from_dhtmlxq(
+            "[DhtmlXQ]\n\
+             [DhtmlXQ_movelist]79677062677962707967706267796270\
+             7747[/DhtmlXQ_movelist]\n\
+             [DhtmlXQ_move_0_9_1]17477062[/DhtmlXQ_move_0_9_1]\n\
+             [/DhtmlXQ]",
+        )
+        .unwrap();
+        assert_eq!(manager.history.moves().count(), 9);
+        assert!(!manager.state.is_ended);
+        assert_eq!(manager.tree.children(8).len(), 2);
+
+        let variation = manager.tree.children(8)[1];
+        let reply = manager.tree.next(variation).unwrap();
+        manager.goto_node(reply).unwrap();
+        assert_eq!(manager.history.moves().count(), 10);
+        assert!(!manager.state.is_ended);
+    }
+
+    #[test]
+    fn test_errors() {
+        let cases = [
+            ("no tags here", "no DhtmlXQ tags found"),
+            ("[DhtmlXQ_title]x", "unterminated tag 'title'"),
+            ("[DhtmlXQ_ title]x[/DhtmlXQ_ title]", "malformed tag"),
+            ("[DhtmlXQ_movelist]77477[/DhtmlXQ_movelist]", "malformed move list '77477'"),
+            ("[DhtmlXQ_movelist]7a47[/DhtmlXQ_movelist]", "malformed move '7a47'"),
+            ("[DhtmlXQ_movelist]77717062[/DhtmlXQ_movelist]", "illegal move h2h8"),
+            ("[DhtmlXQ_movelist]77477967[/DhtmlXQ_movelist]", "illegal move h0g2"),
+            ("[DhtmlXQ_binit]8979[/DhtmlXQ_binit]", "binit must list 32 squares"),
+            ("[DhtmlXQ_move_0_x_1]7747[/DhtmlXQ_move_0_x_1]", "malformed tag 'move_0_x_1'"),
+            (
+                "[DhtmlXQ_move_3_1_1]7747[/DhtmlXQ_move_3_1_1]",
+                "variation 1 branches from a missing line",
+            ),
+            (
+                "[DhtmlXQ_movelist]7747[/DhtmlXQ_movelist][DhtmlXQ_move_0_5_1]7062[/DhtmlXQ_move_0_5_1]",
+                "variation 1 starts after the end of line 0",
+            ),
+        ];
+        for (text, reason) in cases {
+            assert_eq!(
+                parse(text),
+                Err(ChessError::InvalidDhtmlXq(reason.to_string())),
+                "{}",
+                text
+            );
+        }
+    }
+}
@@ -0,0 +1,338 @@
+use crate::board::Board;
+use crate::game::{GameState, GameStateManager};
+use crate::piece::{Color, Piece, PieceType};
+use crate::ChessError;
+use std::str::FromStr;
+
+/// FEN of the standard starting position.
+pub const START_FEN: &str = "rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C5C1/9/RNBAKABNR w - - 0 1";
+
+/// Parses a xiangqi FEN. Rows run from Black's back rank (y = 0) down to
+/// Red's, uppercase letters are Red. The side to move and both move counters
+/// are optional and default to Red, 0 and 1.
+pub fn parse(fen: &str) -> Result<GameState, ChessError> {
+    let mut fields = fen.split_whitespace();
+    let placement = fields
+        .next()
+        .ok_or_else(|| invalid("empty string".to_string()))?;
+    let board = parse_placement(placement)?;
+
+    let current_turn = match fields.next() {
+        None | Some("w") | Some("r") => Color::Red,
+        Some("b") => Color::Black,
+        Some(side) => return Err(invalid(format!("unknown side to move '{}'", side))),
+    };
+
+    // Castling and en passant fields are always "-" in xiangqi
+    for name in ["castling", "en passant"] {
+        match fields.next() {
+            None | Some("-") => {}
+            Some(field) => return Err(invalid(format!("unexpected {} field '{}'", name, field))),
+        }
+    }
+
+    let no_capture_plies = parse_counter(fields.next(), "halfmove clock", 0)?;
+    let move_number = parse_counter(fields.next(), "move number", 1)?;
+    if move_number == 0 {
+        return Err(invalid("move number must start at 1".to_string()));
+    }
+    if let Some(extra) = fields.next() {
+        return Err(invalid(format!("unexpected trailing field '{}'", extra)));
+    }
+
+    let mut state = GameState::from_position(board, current_turn)?;
+    state.no_capture_plies = no_capture_plies;
+    state.move_number = move_number;
+    Ok(state)
+}
+
+/// Serializes the position, side to move and move counters as FEN.
+pub fn to_fen(state: &GameState) -> String {
+    let mut rows = Vec::with_capacity(10);
+    for y in 0..10 {
+        let mut row = String::new();
+        let mut empty = 0;
+        for x in 0..9 {
+            match state.board.get_piece(x, y) {
+                Some(piece) => {
+                    if empty > 0 {
+                        row.push_str(&empty.to_string());
+                        empty = 0;
+                    }
+                    row.push(piece_to_char(piece));
+                }
+                None => empty += 1,
+            }
+        }
+        if empty > 0 {
+            row.push_str(&empty.to_string());
+        }
+        rows.push(row);
+    }
+
+    let side = match state.current_turn {
+        Color::Red => 'w',
+        Color::Black => 'b',
+    };
+    format!(
+        "{} {} - - {} {}",
+        rows.join("/"),
+        side,
+        state.no_capture_plies,
+        state.move_number
+    )
+}
+
+fn parse_placement(placement: &str) -> Result<Board, ChessError> {
+    let rows: Vec<&str> = placement.split('/').collect();
+    if rows.len() != 10 {
+        return Err(invalid(format!("expected 10 rows, found {}", rows.len())));
+    }
+
+    let mut board = Board::new();
+    for (y, row) in rows.iter().enumerate() {
+        let mut x = 0;
+        for c in row.chars() {
+            x += match c.to_digit(10) {
+                Some(0) => return Err(invalid(format!("empty run of 0 in row {}", y + 1))),
+                Some(empty) => empty as usize,
+                None => {
+                    let piece = piece_from_char(c).ok_or_else(|| {
+                        invalid(format!("unknown piece '{}' in row {}", c, y + 1))
+                    })?;
+                    if x < 9 {
+                        board.set_piece(x, y, Some(piece));
+                    }
+                    1
+                }
+            };
+            if x > 9 {
+                break;
+            }
+        }
+        if x != 9 {
+            return Err(invalid(format!("row {} does not have 9 files", y + 1)));
+        }
+    }
+    Ok(board)
+}
+
+fn parse_counter(field: Option<&str>, name: &str, default: usize) -> Result<usize, ChessError> {
+    match field {
+        None => Ok(default),
+        Some(value) => value
+            .parse()
+            .map_err(|_| invalid(format!("bad {} '{}'", name, value))),
+    }
+}
+
+// Letters follow UCCI: K general, A advisor, B elephant, N horse, R chariot,
+// C cannon, P soldier. E and H are accepted as elephant and horse.
+fn piece_from_char(c: char) -> Option<Piece> {
+    let piece_type = match c.to_ascii_uppercase() {
+        'K' => PieceType::General,
+        'A' => PieceType::Advisor,
+        'B' | 'E' => PieceType::Elephant,
+        'N' | 'H' => PieceType::Horse,
+        'R' => PieceType::Chariot,
+        'C' => PieceType::Cannon,
+        'P' => PieceType::Soldier,
+        _ => return None,
+    };
+    let color = if c.is_ascii_uppercase() {
+        Color::Red
+    } else {
+        Color::Black
+    };
+    Some(Piece::new(piece_type, color))
+}
+
+fn piece_to_char(piece: Piece) -> char {
+    let c = match piece.piece_type {
+        PieceType::General => 'K',
+        PieceType::Advisor => 'A',
+        PieceType::Elephant => 'B',
+        PieceType::Horse => 'N',
+        PieceType::Chariot => 'R',
+        PieceType::Cannon => 'C',
+        PieceType::Soldier => 'P',
+    };
+    match piece.color {
+        Color::Red => c,
+        Color::Black => c.to_ascii_lowercase(),
+    }
+}
+
+fn invalid(reason: String) -> ChessError {
+    ChessError::InvalidFen(reason)
+}
+
+impl GameState {
+    /// Creates a game state from a FEN string.
+    pub fn from_fen(fen: &str) -> Result<Self, ChessError> {
+        parse(fen)
+    }
+
+    /// Serializes the game state as FEN.
+    pub fn to_fen(&self) -> String {
+        to_fen(self)
+    }
+}
+
+impl FromStr for GameState {
+    type Err = ChessError;
+
+    fn from_str(fen: &str) -> Result<Self, Self::Err> {
+        parse(fen)
+    }
+}
+
+impl GameStateManager {
+    /// Starts a game from a FEN string with an empty history.
+    pub fn from_fen(fen: &str) -> Result<Self, ChessError> {
+        let mut manager = Self::from_state(parse(fen)?);
+        manager.update_status();
+        Ok(manager)
+    }
+
+    /// FEN of the position the game started from.
+    pub fn start_fen(&self) -> String {
+        let start = &self.positions[0];
+        let black_moves = self
+            .history
+            .moves()
+            .filter(|record| record.piece.color == Color::Black)
+            .count();
+        let mut state = GameState::from_board(start.board.clone(), start.side_to_move);
+        state.no_capture_plies = start.no_capture_plies;
+        state.move_number = self.state.move_number - black_moves;
+        to_fen(&state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_start_position() {
+        let state = GameState::from_fen(START_FEN).unwrap();
+        assert_eq!(state, GameState::new());
+        assert_eq!(GameState::new().to_fen(), START_FEN);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let fens = [
+            "r1ba1a3/4kn3/2n1b4/pNp1p1p1p/4c4/6P2/P1P2R2P/1CcC5/9/2BAKAB2 w - - 0 1",
+            "4k4/9/9/9/9/9/9/9/4A4/3K1C3 b - - 12 40",
+            "3k5/4a4/9/9/2b6/9/9/9/4p4/5K3 w - - 0 73",
+        ];
+        for fen in fens {
+            assert_eq!(GameState::from_fen(fen).unwrap().to_fen(), fen);
+        }
+    }
+
+    #[test]
+    fn test_orientation_and_counters() {
+        let state: GameState = "4k4/9/9/9/9/9/9/9/4A4/3K1C3 b - - 12 40".parse().unwrap();
+        assert_eq!(
+            state.board.get_piece(4, 0),
+            Some(Piece::new(PieceType::General, Color::Black))
+        );
+        assert_eq!(
+            state.board.get_piece(3, 9),
+            Some(Piece::new(PieceType::General, Color::Red))
+        );
+        assert_eq!(state.current_turn, Color::Black);
+        assert_eq!(state.no_capture_plies, 12);
+        assert_eq!(state.move_number, 40);
+        assert_eq!(state.board.hash(), state.board.compute_hash());
+
+        // Optional trailing fields and the alternative letters
+        let short = GameState::from_fen("4k4/9/9/9/9/9/9/9/4A4/3K1H3").unwrap();
+        assert_eq!(short.current_turn, Color::Red);
+        assert_eq!(short.move_number, 1);
+        assert_eq!(short.to_fen(), "4k4/9/9/9/9/9/9/9/4A4/3K1N3 w - - 0 1");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let cases = [
+            ("", "empty string"),
+            ("4k4/9/9/9/9/9/9/9/3K5 w", "expected 10 rows, found 9"),
+            ("4k4/9/9/9/9/9/9/9/9/3K4 w", "row 10 does not have 9 files"),
+            ("4k5/9/9/9/9/9/9/9/9/3K5 w", "row 1 does not have 9 files"),
+            ("04k4/9/9/9/9/9/9/9/9/3K5 w", "empty run of 0 in row 1"),
+            ("4k40/9/9/9/9/9/9/9/9/3K5 w", "empty run of 0 in row 1"),
+            ("4k4/9/9/9/9/9/9/9/9/3K5R w", "row 10 does not have 9 files"),
+            ("4x4/9/9/9/9/9/9/9/9/3K5 w", "unknown piece 'x' in row 1"),
+            ("4k4/9/9/9/9/9/9/9/9/3K5 x", "unknown side to move 'x'"),
+            (
+                "4k4/9/9/9/9/9/9/9/9/3K5 w - - x 1",
+                "bad halfmove clock 'x'",
+            ),
+            (
+                "4k4/9/9/9/9/9/9/9/9/3K5 w - - 0 0",
+                "move number must start at 1",
+            ),
+            (
+                "4k4/9/9/9/9/9/9/9/9/3K5 w - - 0 1 2",
+                "unexpected trailing field '2'",
+            ),
+        ];
+        for (fen, reason) in cases {
+            assert_eq!(
+                GameState::from_fen(fen),
+                Err(ChessError::InvalidFen(reason.to_string())),
+                "{}",
+                fen
+            );
+        }
+
+        // Well-formed but impossible positions are rejected by the validator
+        assert!(matches!(
+            GameState::from_fen("4k4/9/9/9/9/9/9/9/9/4K4 w"),
+            Err(ChessError::InvalidPosition(_))
+        ));
+    }
+
+    #[test]
+    fn test_manager_from_fen() {
+        // Black is checkmated by the two chariots
+        let manager = GameStateManager::from_fen("R3k4/8R/9/9/9/9/9/9/9/3K5 b").unwrap();
+        assert!(manager.state.is_in_check);
+        assert!(manager.state.is_ended);
+        assert_eq!(manager.state.winner, Some(Color::Red));
+        assert_eq!(manager.positions.len(), 1);
+    }
+
+    #[test]
+    fn test_counters_follow_moves() {
+        let mut manager = GameStateManager::from_fen(START_FEN).unwrap();
+        manager.make_move(7, 7, 4, 7).unwrap();
+        manager.make_move(7, 0, 6, 2).unwrap();
+        assert_eq!(
+            manager.state.to_fen(),
+            "rnbakab1r/9/1c4nc1/p1p1p1p1p/9/9/P1P1P1P1P/1C2C4/9/RNBAKABNR w - - 2 2"
+        );
+
+        manager.undo_move().unwrap();
+        assert_eq!(
+            manager.state.to_fen(),
+            "rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C2C4/9/RNBAKABNR b - - 1 1"
+        );
+        assert_eq!(manager.start_fen(), START_FEN);
+    }
+
+    #[test]
+    fn test_start_fen_survives_moves() {
+        let fen = "4k4/9/9/9/9/9/9/9/4A4/3K1C3 b - - 12 40";
+        let mut manager = GameStateManager::from_fen(fen).unwrap();
+        manager.make_move(4, 0, 4, 1).unwrap();
+        manager.make_move(5, 9, 5, 1).unwrap();
+        manager.make_move(4, 1, 4, 2).unwrap();
+        assert_eq!(manager.state.move_number, 42);
+        assert_eq!(manager.start_fen(), fen);
+    }
+}
@@ -1,10 +1,77 @@
-use crate::board::Board;
+use crate::attack;
+use crate::board::{Board, Undo};
 use crate::history::{History, MoveRecord};
+use crate::material;
+use crate::movegen;
+use crate::moves::Move;
 use crate::piece::{Color, Piece, PieceType};
+use crate::repetition::{self, Position, RuleSet};
+use crate::rules;
+use crate::square::Square;
+use crate::tree::{GameTree, NodeId, ROOT};
 use crate::validator::MoveValidator;
+use crate::zobrist;
 
 use serde::{Deserialize, Serialize};
 
+/// Why a game ended.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Termination {
+    /// The side to move is in check and has no legal move
+    Checkmate,
+    /// The side to move is not in check but has no legal move (困毙)
+    Stalemate,
+    /// A general was captured
+    GeneralCaptured,
+    /// A player resigned
+    Resignation,
+    /// A player ran out of time
+    Timeout,
+    /// Both players agreed to a draw
+    AgreedDraw,
+    /// The side that repeated the position with nothing but checks lost (长将)
+    PerpetualCheck,
+    /// The side that repeated the position with chases, or a forbidden mix of
+    /// checks, chases and mate threats, lost (长捉)
+    PerpetualChase,
+    /// The position repeated three times with no single side at fault
+    Repetition,
+    /// The no-capture move limit was reached (自然限着)
+    MoveLimit,
+    /// Neither side has material left to deliver mate
+    InsufficientMaterial,
+}
+
+/// Default number of moves per side without a capture before the game is drawn.
+pub const DEFAULT_NO_CAPTURE_LIMIT: usize = 60;
+
+/// Final outcome of a game. `winner` is `None` for a draw.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GameResult {
+    pub winner: Option<Color>,
+    pub termination: Termination,
+}
+
+impl GameResult {
+    pub fn win(winner: Color, termination: Termination) -> Self {
+        Self {
+            winner: Some(winner),
+            termination,
+        }
+    }
+
+    pub fn draw(termination: Termination) -> Self {
+        Self {
+            winner: None,
+            termination,
+        }
+    }
+
+    pub fn is_draw(&self) -> bool {
+        self.winner.is_none()
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct GameState {
     pub board: Board,
@@ -12,22 +79,86 @@ pub struct GameState {
     pub is_in_check: bool,
     pub is_ended: bool,
     pub winner: Option<Color>,
+    pub result: Option<GameResult>,
+    /// Plies played since the last capture
+    pub no_capture_plies: usize,
+    /// Full move number, starting at 1 and incremented after each Black move
+    pub move_number: usize,
 }
 
 impl GameState {
     pub fn new() -> Self {
         let mut board = Board::new();
         Self::initialize_pieces(&mut board);
+        Self::from_board(board, Color::Red)
+    }
 
+    /// Creates an ongoing game from an arbitrary position.
+    pub fn from_board(board: Board, current_turn: Color) -> Self {
         Self {
             board,
-            current_turn: Color::Red,
+            current_turn,
             is_in_check: false,
             is_ended: false,
             winner: None,
+            result: None,
+            no_capture_plies: 0,
+            move_number: 1,
         }
     }
 
+    /// Creates an ongoing game from a position, rejecting impossible placements.
+    pub fn from_position(board: Board, current_turn: Color) -> Result<Self, crate::ChessError> {
+        rules::validate_position(&board, current_turn)?;
+        let mut state = Self::from_board(board, current_turn);
+        state.is_in_check = rules::is_in_check(&state.board, current_turn);
+        Ok(state)
+    }
+
+    /// Zobrist key of the position, covering piece placement and side to move.
+    pub fn zobrist_key(&self) -> u64 {
+        self.board.hash() ^ zobrist::side_key(self.current_turn)
+    }
+
+    /// Makes a move on the board and updates the capture counter. The returned
+    /// token restores the board and every flag, including the side to move.
+    pub fn make_move(&mut self, mv: Move) -> StateUndo {
+        let undo = StateUndo {
+            board: self.board.make_move(mv),
+            current_turn: self.current_turn,
+            is_in_check: self.is_in_check,
+            result: self.result,
+            no_capture_plies: self.no_capture_plies,
+            move_number: self.move_number,
+        };
+        self.no_capture_plies = if undo.board.captured.is_some() {
+            0
+        } else {
+            self.no_capture_plies + 1
+        };
+        if self.current_turn == Color::Black {
+            self.move_number += 1;
+        }
+        undo
+    }
+
+    /// Takes back a move made with `make_move`.
+    pub fn unmake_move(&mut self, undo: StateUndo) {
+        self.board.unmake_move(undo.board);
+        self.current_turn = undo.current_turn;
+        self.is_in_check = undo.is_in_check;
+        self.set_result(undo.result);
+        self.no_capture_plies = undo.no_capture_plies;
+        self.move_number = undo.move_number;
+    }
+
+    /// Sets the game result, keeping `is_ended` and `winner` in sync with it.
+    pub fn set_result(&mut self, result: Option<GameResult>) {
+        self.is_ended = result.is_some();
+        self.winner = result.and_then(|r| r.winner);
+        self.result = result;
+    }
+
     fn initialize_pieces(board: &mut Board) {
         // Black pieces (top, y=0)
         board.set_piece(0, 0, Some(Piece::new(PieceType::Chariot, Color::Black)));
@@ -71,6 +202,17 @@ impl GameState {
     }
 }
 
+/// Everything needed to take back a move made with `GameState::make_move`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StateUndo {
+    pub board: Undo,
+    pub current_turn: Color,
+    pub is_in_check: bool,
+    pub result: Option<GameResult>,
+    pub no_capture_plies: usize,
+    pub move_number: usize,
+}
+
 impl Default for GameState {
     fn default() -> Self {
         Self::new()
@@ -81,6 +223,22 @@ impl Default for GameState {
 pub struct GameStateManager {
     pub state: GameState,
     pub history: History,
+    /// Rule set used to adjudicate repeated positions
+    pub rule_set: RuleSet,
+    /// Every position reached so far, starting with the initial one
+    pub positions: Vec<Position>,
+    /// Moves per side without a capture before the game is drawn, if enforced
+    pub no_capture_limit: Option<usize>,
+    /// Undo tokens for the moves in `history`, most recent last
+    undo_stack: Vec<StateUndo>,
+    /// Every move played or taken back, with its variations
+    pub tree: GameTree,
+    /// Node of the position on the board; `history` is the line leading to it
+    pub(crate) current: NodeId,
+    /// Moves taken back with `undo_move` and the result each one led to,
+    /// the most recent last. Navigating the tree keeps them; playing a new
+    /// move drops them
+    pub(crate) redo_stack: Vec<(NodeId, Option<GameResult>)>,
 }
 
 impl Clone for GameStateManager {
@@ -88,18 +246,64 @@ impl Clone for GameStateManager {
         Self {
             state: self.state.clone(),
             history: self.history.clone(),
+            rule_set: self.rule_set,
+            positions: self.positions.clone(),
+            no_capture_limit: self.no_capture_limit,
+            undo_stack: self.undo_stack.clone(),
+            tree: self.tree.clone(),
+            current: self.current,
+            redo_stack: self.redo_stack.clone(),
         }
     }
 }
 
 impl GameStateManager {
     pub fn new() -> Self {
+        Self::from_state(GameState::new())
+    }
+
+    /// Starts a game from the given state with an empty history.
+    pub fn from_state(state: GameState) -> Self {
+        let positions = vec![Position::new(
+            state.board.clone(),
+            state.current_turn,
+            state.no_capture_plies,
+        )];
         Self {
-            state: GameState::new(),
+            state,
             history: History::new(),
+            rule_set: RuleSet::default(),
+            positions,
+            no_capture_limit: Some(DEFAULT_NO_CAPTURE_LIMIT),
+            undo_stack: Vec::new(),
+            tree: GameTree::new(),
+            current: ROOT,
+            redo_stack: Vec::new(),
+        }
+    }
+
+    /// Selects the rule set used to adjudicate repeated positions.
+    pub fn with_rule_set(mut self, rule_set: RuleSet) -> Self {
+        self.rule_set = rule_set;
+        self
+    }
+
+    /// Switches the rule set during a game. A game still in progress is
+    /// re-evaluated under the new rules; a finished one keeps its result.
+    pub fn set_rule_set(&mut self, rule_set: RuleSet) {
+        self.rule_set = rule_set;
+        if !self.state.is_ended {
+            self.update_status();
         }
     }
 
+    /// Sets how many moves per side without a capture draw the game; `None` disables the limit.
+    pub fn with_no_capture_limit(mut self, limit: Option<usize>) -> Self {
+        self.no_capture_limit = limit;
+        self
+    }
+
+    /// Makes a move given as (x, y) coordinates.
     pub fn make_move(
         &mut self,
         from_x: usize,
@@ -107,63 +311,72 @@ impl GameStateManager {
         to_x: usize,
         to_y: usize,
     ) -> Result<(), crate::ChessError> {
+        self.play_move(Move::from_coords(from_x, from_y, to_x, to_y)?)
+    }
+
+    /// Plays a move. Moves taken back before can no longer be redone.
+    pub fn play_move(&mut self, mv: Move) -> Result<(), crate::ChessError> {
+        self.apply_move(mv)?;
+        self.redo_stack.clear();
+        Ok(())
+    }
+
+    fn apply_move(&mut self, mv: Move) -> Result<(), crate::ChessError> {
         if self.state.is_ended {
             return Err(crate::ChessError::GameEnded);
         }
+        self.replay_move(mv)?;
+        if !self.state.is_ended {
+            self.update_status();
+        }
+        Ok(())
+    }
+
+    /// Plays a recorded move without judging the position it leads to, so
+    /// that a game can be replayed past a repetition or move limit at which
+    /// the players went on anyway. Only a captured general stops the replay.
+    /// The position is left without a result; call `update_status` once the
+    /// replay is done.
+    pub(crate) fn replay_move(&mut self, mv: Move) -> Result<(), crate::ChessError> {
+        if matches!(self.state.result, Some(result) if result.termination == Termination::GeneralCaptured)
+        {
+            return Err(crate::ChessError::GameEnded);
+        }
 
         // Validate move
-        MoveValidator::validate(
-            &self.state.board,
-            from_x,
-            from_y,
-            to_x,
-            to_y,
-            self.state.current_turn,
-        )?;
+        MoveValidator::validate(&mut self.state.board, mv, self.state.current_turn)?;
 
         // Get the piece being moved
         let piece = self
             .state
             .board
-            .get_piece(from_x, from_y)
+            .piece_at(mv.from())
             .ok_or(crate::ChessError::InvalidMove)?;
 
         // Make move
-        let captured_piece = self.state.board.move_piece(from_x, from_y, to_x, to_y);
+        let undo = self.state.make_move(mv);
+        let captured_piece = undo.board.captured;
+        self.undo_stack.push(undo);
+        self.state.set_result(None);
 
         // Check if captured the opponent's general (game ends immediately)
-        if let Some(piece) = captured_piece {
-            if piece.piece_type == PieceType::General {
-                self.state.is_ended = true;
-                self.state.winner = Some(self.state.current_turn);
-                // Record move
-                self.history.push_with_color(
-                    MoveRecord {
-                        from_x,
-                        from_y,
-                        to_x,
-                        to_y,
-                        piece,
-                        captured_piece,
-                    },
+        if let Some(captured) = captured_piece {
+            if captured.piece_type == PieceType::General {
+                self.state.set_result(Some(GameResult::win(
                     self.state.current_turn,
-                );
+                    Termination::GeneralCaptured,
+                )));
+                self.positions.push(Position::new(
+                    self.state.board.clone(),
+                    self.state.current_turn.opponent(),
+                    self.state.no_capture_plies,
+                ));
+                self.record_move(MoveRecord::new(mv, piece, captured_piece));
                 return Ok(());
             }
         }
 
-        // Record move
-        self.history.push_with_color(
-            MoveRecord {
-                from_x,
-                from_y,
-                to_x,
-                to_y,
-                piece,
-                captured_piece,
-            },
-            self.state.current_turn,
-        );
+        self.record_move(MoveRecord::new(mv, piece, captured_piece));
 
         // Switch turn
         self.state.current_turn = match self.state.current_turn {
@@ -171,146 +384,196 @@ impl GameStateManager {
             Color::Black => Color::Red,
         };
 
-        // Check if in check
+        self.positions.push(Position::new(
+            self.state.board.clone(),
+            self.state.current_turn,
+            self.state.no_capture_plies,
+        ));
         self.state.is_in_check = self.is_in_check(self.state.current_turn);
+        debug_assert_eq!(self.state.board.hash(), self.state.board.compute_hash());
 
-        // Check if game ended (checkmate)
-        if self.is_checkmate(self.state.current_turn) {
-            self.state.is_ended = true;
-            self.state.winner = Some(match self.state.current_turn {
-                Color::Red => Color::Black,
-                Color::Black => Color::Red,
-            });
+        Ok(())
+    }
+
+    // Adds a move to the history and the tree, keeping any line it leaves
+    fn record_move(&mut self, record: MoveRecord) {
+        self.current = self.tree.add_move(self.current, record);
+        // A move played before keeps the annotation it was given
+        let record = self
+            .tree
+            .record(self.current)
+            .cloned()
+            .expect("moves are recorded in the tree");
+        self.history
+            .push_with_color(record, self.state.current_turn);
+    }
+
+    /// Takes back the last move. It stays in the tree, so the line can be
+    /// returned to with `redo_move` or `goto_node`.
+    pub fn undo_move(&mut self) -> Result<(), crate::ChessError> {
+        let undo = self.undo_stack.pop().ok_or(crate::ChessError::NoHistory)?;
+        self.redo_stack.push((self.current, self.state.result));
+        self.history.pop();
+        self.current = self.tree.parent(self.current).unwrap_or(ROOT);
+        if self.positions.len() > 1 {
+            self.positions.pop();
         }
 
+        // Restore the board, side to move and status flags
+        self.state.unmake_move(undo);
+        self.state.is_in_check = self.is_in_check(self.state.current_turn);
+        debug_assert_eq!(self.state.board.hash(), self.state.board.compute_hash());
+
         Ok(())
     }
 
-    pub fn undo_move(&mut self) -> Result<(), crate::ChessError> {
-        if self.history.is_empty() {
-            return Err(crate::ChessError::NoHistory);
+    /// Plays the last move taken back with `undo_move` again, with the
+    /// capture it made and the result the game had after it, even one that
+    /// came from a resignation or an agreed draw.
+    pub fn redo_move(&mut self) -> Result<(), crate::ChessError> {
+        let node = self
+            .redo_target(self.current)
+            .ok_or(crate::ChessError::NothingToRedo)?;
+        if self.state.is_ended {
+            return Err(crate::ChessError::GameEnded);
         }
+        self.replay_node(node).map(|_| ())
+    }
 
-        let (last_move, move_color) = self.history.pop().unwrap();
+    /// The most recent move taken back from `node`, if any.
+    pub(crate) fn redo_target(&self, node: NodeId) -> Option<NodeId> {
+        self.redo_stack
+            .iter()
+            .rev()
+            .map(|&(next, _)| next)
+            .find(|&next| self.tree.parent(next) == Some(node))
+    }
 
-        // Restore piece
-        let piece = self
-            .state
-            .board
-            .get_piece(last_move.to_x, last_move.to_y)
-            .unwrap();
-        self.state
-            .board
-            .set_piece(last_move.from_x, last_move.from_y, Some(piece));
-        self.state
-            .board
-            .set_piece(last_move.to_x, last_move.to_y, last_move.captured_piece);
+    /// Plays the move leading to `node`, a child of the current node. A move
+    /// that was taken back comes off the redo stack with the result it led
+    /// to, and true is returned; any other is replayed unjudged, as by
+    /// `replay_move`.
+    pub(crate) fn replay_node(&mut self, node: NodeId) -> Result<bool, crate::ChessError> {
+        let mv = self
+            .tree
+            .record(node)
+            .map(MoveRecord::mv)
+            .ok_or(crate::ChessError::UnknownNode)?;
+        self.replay_move(mv)?;
+        match self.redo_stack.iter().position(|&(next, _)| next == node) {
+            Some(index) => {
+                let (_, result) = self.redo_stack.remove(index);
+                self.state.set_result(result);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
 
-        // Set turn to the color of the move that was undone
-        self.state.current_turn = move_color;
+    /// Ends the game with the given color resigning.
+    pub fn resign(&mut self, color: Color) -> Result<(), crate::ChessError> {
+        self.finish(GameResult::win(color.opponent(), Termination::Resignation))
+    }
 
-        // Check if in check for the current player
-        self.state.is_in_check = self.is_in_check(self.state.current_turn);
+    /// Ends the game with the given color losing on time.
+    pub fn timeout(&mut self, color: Color) -> Result<(), crate::ChessError> {
+        self.finish(GameResult::win(color.opponent(), Termination::Timeout))
+    }
 
-        // Reset game ended state since we undid a move
-        self.state.is_ended = false;
-        self.state.winner = None;
+    /// Ends the game as a draw agreed by both players.
+    pub fn agree_draw(&mut self) -> Result<(), crate::ChessError> {
+        self.finish(GameResult::draw(Termination::AgreedDraw))
+    }
 
+    fn finish(&mut self, result: GameResult) -> Result<(), crate::ChessError> {
+        if self.state.is_ended {
+            return Err(crate::ChessError::GameEnded);
+        }
+        self.state.set_result(Some(result));
         Ok(())
     }
 
-    pub fn get_valid_moves(&self, x: usize, y: usize) -> Vec<(usize, usize)> {
-        MoveValidator::get_valid_moves(&self.state.board, x, y, self.state.current_turn)
+    /// Recomputes check status and the game result for the side to move.
+    pub(crate) fn update_status(&mut self) {
+        let color = self.state.current_turn;
+
+        // Check if in check
+        self.state.is_in_check = self.is_in_check(color);
+
+        // Check if game ended: a side with no legal move loses, whether it is
+        // checkmated or stalemated
+        let result = if self.has_legal_moves(color) {
+            // Otherwise a repeated position may be decided by the rule set, and
+            // a long stretch without captures or a lack of attacking material is drawn
+            repetition::adjudicate(&mut self.positions, self.rule_set)
+                .or_else(|| {
+                    self.no_capture_limit
+                        .filter(|limit| self.state.no_capture_plies >= limit * 2)
+                        .map(|_| GameResult::draw(Termination::MoveLimit))
+                })
+                .or_else(|| {
+                    material::is_insufficient_material(&self.state.board)
+                        .then(|| GameResult::draw(Termination::InsufficientMaterial))
+                })
+        } else if self.state.is_in_check {
+            Some(GameResult::win(color.opponent(), Termination::Checkmate))
+        } else {
+            Some(GameResult::win(color.opponent(), Termination::Stalemate))
+        };
+        self.state.set_result(result);
     }
 
-    fn is_in_check(&self, color: Color) -> bool {
-        // Find general position
-        let mut general_pos = None;
-        for x in 0..9 {
-            for y in 0..10 {
-                if let Some(piece) = self.state.board.get_piece(x, y) {
-                    if piece.piece_type == PieceType::General && piece.color == color {
-                        general_pos = Some((x, y));
-                        break;
-                    }
-                }
+    /// Returns the legal destinations of the piece on (x, y) as (x, y) pairs.
+    pub fn get_valid_moves(&mut self, x: usize, y: usize) -> Vec<(usize, usize)> {
+        match Square::from_xy(x, y) {
+            Some(from) => {
+                MoveValidator::get_valid_moves(&mut self.state.board, from, self.state.current_turn)
+                    .into_iter()
+                    .map(Square::to_xy)
+                    .collect()
             }
+            None => Vec::new(),
         }
+    }
 
-        if let Some((g_x, g_y)) = general_pos {
-            // Check if any opponent piece can attack general
-            let opponent_color = match color {
-                Color::Red => Color::Black,
-                Color::Black => Color::Red,
-            };
-
-            for x in 0..9 {
-                for y in 0..10 {
-                    if let Some(piece) = self.state.board.get_piece(x, y) {
-                        if piece.color == opponent_color
-                            && MoveValidator::validate(
-                                &self.state.board,
-                                x,
-                                y,
-                                g_x,
-                                g_y,
-                                opponent_color,
-                            )
-                            .is_ok()
-                        {
-                            return true;
-                        }
-                    }
-                }
-            }
+    /// Returns every legal move for the side to move.
+    pub fn legal_moves(&mut self) -> Vec<Move> {
+        movegen::legal_moves(&mut self.state.board, self.state.current_turn)
+    }
+
+    /// Returns the pieces of `color` attacking (x, y) as (x, y) pairs.
+    pub fn get_attackers(&self, x: usize, y: usize, color: Color) -> Vec<(usize, usize)> {
+        match Square::from_xy(x, y) {
+            Some(square) => attack::attackers_of(&self.state.board, square, color)
+                .into_iter()
+                .map(Square::to_xy)
+                .collect(),
+            None => Vec::new(),
         }
+    }
 
-        false
+    /// Returns the pieces giving check to the side to move as (x, y) pairs.
+    pub fn get_checkers(&self) -> Vec<(usize, usize)> {
+        attack::checkers(&self.state.board, self.state.current_turn)
+            .into_iter()
+            .map(Square::to_xy)
+            .collect()
     }
 
-    fn is_checkmate(&self, color: Color) -> bool {
-        // If not in check, not checkmate
-        if !self.is_in_check(color) {
-            return false;
-        }
+    fn is_in_check(&self, color: Color) -> bool {
+        rules::is_in_check(&self.state.board, color)
+    }
 
-        // Check if any piece has valid move that gets out of check
-        for from_x in 0..9 {
-            for from_y in 0..10 {
-                if let Some(piece) = self.state.board.get_piece(from_x, from_y) {
-                    if piece.color == color {
-                        let valid_moves = MoveValidator::get_valid_moves(
-                            &self.state.board,
-                            from_x,
-                            from_y,
-                            color,
-                        );
-                        for (to_x, to_y) in valid_moves {
-                            // Simulate move to check if still in check
-                            let mut temp_board = self.state.board.clone();
-                            temp_board.move_piece(from_x, from_y, to_x, to_y);
-
-                            let temp_manager = GameStateManager {
-                                state: GameState {
-                                    board: temp_board,
-                                    current_turn: color,
-                                    is_in_check: false,
-                                    is_ended: false,
-                                    winner: None,
-                                },
-                                history: self.history.clone(),
-                            };
-
-                            if !temp_manager.is_in_check(color) {
-                                return false;
-                            }
-                        }
-                    }
-                }
-            }
-        }
+    pub fn is_checkmate(&mut self, color: Color) -> bool {
+        self.is_in_check(color) && !self.has_legal_moves(color)
+    }
 
-        true
+    pub fn is_stalemate(&mut self, color: Color) -> bool {
+        !self.is_in_check(color) && !self.has_legal_moves(color)
+    }
+
+    fn has_legal_moves(&mut self, color: Color) -> bool {
+        MoveValidator::has_legal_moves(&mut self.state.board, color)
     }
 }
 
@@ -354,18 +617,9 @@ mod tests {
         board.set_piece(4, 1, Some(Piece::new(PieceType::Chariot, Color::Red)));
 
         // 创建游戏状态
-        let state = GameState {
-            board,
-            current_turn: Color::Red,
-            is_in_check: false,
-            is_ended: false,
-            winner: None,
-        };
+        let state = GameState::from_board(board, Color::Red);
 
-        let mut manager = GameStateManager {
-            state,
-            history: History::new(),
-        };
+        let mut manager = GameStateManager::from_state(state);
 
         println!("初始状态: 红方回合，黑将在 (4,0)，红车在 (4,1)");
 
@@ -381,9 +635,46 @@ mod tests {
         assert!(manager.state.is_ended);
         assert_eq!(manager.state.winner, Some(Color::Red));
 
+        // 记录的走子是红车，被吃的是黑将
+        let record = &manager.history.rounds[0].red_move;
+        assert_eq!(record.piece, Piece::new(PieceType::Chariot, Color::Red));
+        assert_eq!(
+            record.captured_piece,
+            Some(Piece::new(PieceType::General, Color::Black))
+        );
+        assert!(manager.history.rounds[0].black_move.is_none());
+
         println!("\n=== 测试成功！吃将后游戏立即结束 ===\n");
     }
 
+    #[test]
+    fn test_undo_restores_state_exactly() {
+        println!("=== 测试撤销后完整恢复局面 ===\n");
+
+        let mut board = Board::new();
+        board.set_piece(4, 0, Some(Piece::new(PieceType::General, Color::Black)));
+        board.set_piece(4, 1, Some(Piece::new(PieceType::Chariot, Color::Red)));
+        board.set_piece(3, 9, Some(Piece::new(PieceType::General, Color::Red)));
+
+        let mut manager = GameStateManager::from_state(GameState::from_board(board, Color::Red));
+        let before = manager.state.clone();
+
+        println!("1. 红车吃将，游戏结束");
+        assert!(manager.make_move(4, 1, 4, 0).is_ok());
+        assert!(manager.state.is_ended);
+
+        println!("2. 撤销吃将，局面与标志全部恢复");
+        assert!(manager.undo_move().is_ok());
+        assert_eq!(manager.state, before);
+        assert_eq!(manager.state.zobrist_key(), before.zobrist_key());
+        assert_eq!(manager.positions.len(), 1);
+
+        println!("3. 再次撤销应失败");
+        assert_eq!(manager.undo_move(), Err(crate::ChessError::NoHistory));
+
+        println!("\n=== 测试成功！撤销后局面完整恢复 ===\n");
+    }
+
     #[test]
     fn test_undo_move_correct_turn() {
         println!("=== 测试撤销移动后的正确回合 ===\n");
@@ -395,18 +686,9 @@ mod tests {
         // 黑方卒在 (8,3)，可以向前移动
         board.set_piece(8, 3, Some(Piece::new(PieceType::Soldier, Color::Black)));
 
-        let state = GameState {
-            board,
-            current_turn: Color::Red,
-            is_in_check: false,
-            is_ended: false,
-            winner: None,
-        };
+        let state = GameState::from_board(board, Color::Red);
 
-        let mut manager = GameStateManager {
-            state,
-            history: History::new(),
-        };
+        let mut manager = GameStateManager::from_state(state);
 
         println!("初始状态: 红方回合");
         assert_eq!(manager.state.current_turn, Color::Red);
@@ -507,18 +789,9 @@ mod tests {
         let mut board = Board::new();
         board.set_piece(0, 6, Some(Piece::new(PieceType::Soldier, Color::Red)));
 
-        let state = GameState {
-            board,
-            current_turn: Color::Red,
-            is_in_check: false,
-            is_ended: false,
-            winner: None,
-        };
+        let state = GameState::from_board(board, Color::Red);
 
-        let mut manager = GameStateManager {
-            state,
-            history: History::new(),
-        };
+        let mut manager = GameStateManager::from_state(state);
 
         println!("初始状态: 红方回合");
         assert_eq!(manager.state.current_turn, Color::Red);
@@ -588,18 +861,9 @@ mod tests {
         board.set_piece(7, 0, Some(Piece::new(PieceType::Horse, Color::Black)));
         board.set_piece(8, 3, Some(Piece::new(PieceType::Soldier, Color::Black)));
 
-        let state = GameState {
-            board,
-            current_turn: Color::Red,
-            is_in_check: false,
-            is_ended: false,
-            winner: None,
-        };
+        let state = GameState::from_board(board, Color::Red);
 
-        let mut manager = GameStateManager {
-            state,
-            history: History::new(),
-        };
+        let mut manager = GameStateManager::from_state(state);
 
         println!("初始状态: 红方回合");
         assert_eq!(manager.state.current_turn, Color::Red);
@@ -756,4 +1020,460 @@ mod tests {
 
         println!("\n=== 测试成功！修复后的撤销逻辑正确 ===\n");
     }
+
+    #[test]
+    fn test_facing_generals_count_as_check() {
+        println!("=== 测试将帅对面视为将军 ===\n");
+
+        // 红帅与黑将同在第 4 列，中间只有红马
+        let mut board = Board::new();
+        board.set_piece(4, 0, Some(Piece::new(PieceType::General, Color::Black)));
+        board.set_piece(4, 9, Some(Piece::new(PieceType::General, Color::Red)));
+        board.set_piece(4, 5, Some(Piece::new(PieceType::Horse, Color::Red)));
+        board.set_piece(0, 3, Some(Piece::new(PieceType::Soldier, Color::Black)));
+
+        let state = GameState::from_board(board, Color::Red);
+
+        let mut manager = GameStateManager::from_state(state);
+
+        assert!(!manager.is_in_check(Color::Red));
+        assert!(!manager.is_in_check(Color::Black));
+
+        // 红马离开中路会让将帅对面，必须被拒绝
+        println!("1. 红马 (4,5) -> (3,7) 应被拒绝");
+        assert_eq!(
+            manager.make_move(4, 5, 3, 7),
+            Err(crate::ChessError::GeneralsFacing)
+        );
+
+        // 移除红马后，双方都处于被将状态
+        manager.state.board.set_piece(4, 5, None);
+        assert!(manager.is_in_check(Color::Red));
+        assert!(manager.is_in_check(Color::Black));
+
+        println!("\n=== 测试成功！将帅对面被正确识别 ===\n");
+    }
+
+    #[test]
+    fn test_cannot_leave_general_in_check() {
+        println!("=== 测试不能送将 ===\n");
+
+        // 黑车在中路，被红马挡住
+        let mut board = Board::new();
+        board.set_piece(3, 0, Some(Piece::new(PieceType::General, Color::Black)));
+        board.set_piece(4, 9, Some(Piece::new(PieceType::General, Color::Red)));
+        board.set_piece(4, 7, Some(Piece::new(PieceType::Horse, Color::Red)));
+        board.set_piece(4, 2, Some(Piece::new(PieceType::Chariot, Color::Black)));
+
+        let state = GameState::from_board(board, Color::Red);
+
+        let mut manager = GameStateManager::from_state(state);
+
+        // 红马被牵制，不能离开中路
+        println!("1. 红马 (4,7) -> (2,6) 应被拒绝");
+        assert!(manager.get_valid_moves(4, 7).is_empty());
+        assert_eq!(
+            manager.make_move(4, 7, 2, 6),
+            Err(crate::ChessError::InCheck)
+        );
+        assert!(manager.history.is_empty());
+        assert_eq!(manager.state.current_turn, Color::Red);
+
+        // 红帅不能走到黑车的攻击线上，但可以横移
+        let general_moves = manager.get_valid_moves(4, 9);
+        println!("2. 红帅可走: {:?}", general_moves);
+        assert!(general_moves.contains(&(5, 9)));
+        assert!(general_moves.contains(&(4, 8)));
+        assert!(!general_moves.contains(&(3, 9)));
+
+        println!("\n=== 测试成功！送将被正确拒绝 ===\n");
+    }
+
+    #[test]
+    fn test_stalemate_ends_game_as_loss() {
+        println!("=== 测试困毙判负 ===\n");
+
+        // 黑将在 (4,0)，红车、红马、红帅封住所有去路
+        let mut board = Board::new();
+        board.set_piece(4, 0, Some(Piece::new(PieceType::General, Color::Black)));
+        board.set_piece(3, 9, Some(Piece::new(PieceType::General, Color::Red)));
+        board.set_piece(0, 5, Some(Piece::new(PieceType::Chariot, Color::Red)));
+        board.set_piece(6, 2, Some(Piece::new(PieceType::Horse, Color::Red)));
+
+        let mut manager = GameStateManager::from_state(GameState::from_board(board, Color::Red));
+
+        println!("1. 红车 (0,5) -> (0,1)，黑方无子可动");
+        assert!(manager.make_move(0, 5, 0, 1).is_ok());
+        assert!(!manager.state.is_in_check);
+        assert!(manager.state.is_ended);
+        assert_eq!(manager.state.winner, Some(Color::Red));
+        assert_eq!(
+            manager.state.result.map(|r| r.termination),
+            Some(Termination::Stalemate)
+        );
+
+        // 红帅在 (4,9)，黑车、黑马、黑将封住所有去路
+        let mut board = Board::new();
+        board.set_piece(4, 9, Some(Piece::new(PieceType::General, Color::Red)));
+        board.set_piece(3, 0, Some(Piece::new(PieceType::General, Color::Black)));
+        board.set_piece(0, 4, Some(Piece::new(PieceType::Chariot, Color::Black)));
+        board.set_piece(6, 7, Some(Piece::new(PieceType::Horse, Color::Black)));
+
+        let mut manager = GameStateManager::from_state(GameState::from_board(board, Color::Black));
+
+        println!("2. 黑车 (0,4) -> (0,8)，红方无子可动");
+        assert!(manager.make_move(0, 4, 0, 8).is_ok());
+        assert!(!manager.state.is_in_check);
+        assert!(manager.state.is_ended);
+        assert_eq!(manager.state.winner, Some(Color::Black));
+        assert_eq!(
+            manager.state.result.map(|r| r.termination),
+            Some(Termination::Stalemate)
+        );
+
+        println!("\n=== 测试成功！困毙被正确判负 ===\n");
+    }
+
+    #[test]
+    fn test_checkmate_termination() {
+        println!("=== 测试将死 ===\n");
+
+        // 红车守住第 1 行，另一红车沿底线将军
+        let mut board = Board::new();
+        board.set_piece(4, 0, Some(Piece::new(PieceType::General, Color::Black)));
+        board.set_piece(3, 9, Some(Piece::new(PieceType::General, Color::Red)));
+        board.set_piece(8, 1, Some(Piece::new(PieceType::Chariot, Color::Red)));
+        board.set_piece(0, 5, Some(Piece::new(PieceType::Chariot, Color::Red)));
+
+        let mut manager = GameStateManager::from_state(GameState::from_board(board, Color::Red));
+
+        println!("1. 红车 (0,5) -> (0,0) 将军");
+        assert!(manager.make_move(0, 5, 0, 0).is_ok());
+        assert!(manager.state.is_in_check);
+        assert_eq!(manager.get_checkers(), vec![(0, 0)]);
+        assert_eq!(manager.get_attackers(4, 1, Color::Red), vec![(8, 1)]);
+        assert!(manager.is_checkmate(Color::Black));
+        assert!(!manager.is_stalemate(Color::Black));
+        assert!(manager.state.is_ended);
+        assert_eq!(manager.state.winner, Some(Color::Red));
+        assert_eq!(
+            manager.state.result,
+            Some(GameResult::win(Color::Red, Termination::Checkmate))
+        );
+
+        println!("\n=== 测试成功！将死被正确识别 ===\n");
+    }
+
+    #[test]
+    fn test_undo_restores_game_result() {
+        println!("=== 测试撤销恢复对局结果 ===\n");
+
+        let mut board = Board::new();
+        board.set_piece(4, 0, Some(Piece::new(PieceType::General, Color::Black)));
+        board.set_piece(3, 9, Some(Piece::new(PieceType::General, Color::Red)));
+        board.set_piece(8, 1, Some(Piece::new(PieceType::Chariot, Color::Red)));
+        board.set_piece(0, 5, Some(Piece::new(PieceType::Chariot, Color::Red)));
+
+        let mut manager = GameStateManager::from_state(GameState::from_board(board, Color::Red));
+
+        println!("1. 红车 (0,5) -> (0,0) 将死");
+        assert!(manager.make_move(0, 5, 0, 0).is_ok());
+        assert_eq!(
+            manager.state.result,
+            Some(GameResult::win(Color::Red, Termination::Checkmate))
+        );
+        assert_eq!(
+            manager.make_move(4, 0, 5, 0),
+            Err(crate::ChessError::GameEnded)
+        );
+
+        println!("2. 撤销将死的一步");
+        assert!(manager.undo_move().is_ok());
+        assert_eq!(manager.state.result, None);
+        assert!(!manager.state.is_ended);
+        assert_eq!(manager.state.winner, None);
+        assert!(!manager.state.is_in_check);
+        assert_eq!(manager.state.current_turn, Color::Red);
+
+        println!("3. 红方认输");
+        assert!(manager.resign(Color::Red).is_ok());
+        assert_eq!(
+            manager.state.result,
+            Some(GameResult::win(Color::Black, Termination::Resignation))
+        );
+        assert!(manager.state.is_ended);
+        assert_eq!(manager.state.winner, Some(Color::Black));
+        assert_eq!(manager.agree_draw(), Err(crate::ChessError::GameEnded));
+
+        println!("\n=== 测试成功！撤销正确恢复对局结果 ===\n");
+    }
+
+    #[test]
+    fn test_redo_restores_moves_exactly() {
+        println!("=== 测试重做完整恢复着法 ===\n");
+
+        let mut board = Board::new();
+        board.set_piece(4, 0, Some(Piece::new(PieceType::General, Color::Black)));
+        board.set_piece(0, 3, Some(Piece::new(PieceType::Soldier, Color::Black)));
+        board.set_piece(8, 3, Some(Piece::new(PieceType::Soldier, Color::Black)));
+        board.set_piece(1, 1, Some(Piece::new(PieceType::Chariot, Color::Red)));
+        board.set_piece(0, 9, Some(Piece::new(PieceType::Chariot, Color::Red)));
+        board.set_piece(3, 9, Some(Piece::new(PieceType::General, Color::Red)));
+        let mut manager = GameStateManager::from_state(GameState::from_board(board, Color::Red));
+
+        println!("1. 红车吃卒，黑卒前进，红车沉底将死");
+        assert!(manager.make_move(0, 9, 0, 3).is_ok());
+        assert!(manager.make_move(8, 3, 8, 4).is_ok());
+        assert!(manager.make_move(0, 3, 0, 0).is_ok());
+        assert_eq!(
+            manager.state.result,
+            Some(GameResult::win(Color::Red, Termination::Checkmate))
+        );
+        let finished = manager.clone();
+
+        println!("2. 连续撤销三步后依次重做");
+        for _ in 0..3 {
+            assert!(manager.undo_move().is_ok());
+        }
+        assert_eq!(
+            manager.state.board.get_piece(0, 3).map(|p| p.color),
+            Some(Color::Black)
+        );
+        for _ in 0..3 {
+            assert!(manager.redo_move().is_ok());
+        }
+        assert_eq!(manager.state, finished.state);
+        assert_eq!(manager.history, finished.history);
+        assert_eq!(manager.positions, finished.positions);
+        assert_eq!(manager.redo_move(), Err(crate::ChessError::NothingToRedo));
+
+        println!("3. 认输后撤销再重做，认输结果也恢复");
+        assert!(manager.undo_move().is_ok());
+        assert!(manager.resign(Color::Black).is_ok());
+        let resigned = manager.state.clone();
+        assert!(manager.undo_move().is_ok());
+        assert!(!manager.state.is_ended);
+        assert!(manager.redo_move().is_ok());
+        assert_eq!(manager.state, resigned);
+
+        println!("4. 撤销后黑将另走一步，重做栈被清空");
+        assert!(manager.undo_move().is_ok());
+        assert!(manager.make_move(4, 0, 5, 0).is_ok());
+        assert_eq!(manager.redo_move(), Err(crate::ChessError::NothingToRedo));
+
+        println!("\n=== 测试成功！重做完整恢复着法 ===\n");
+    }
+
+    #[test]
+    fn test_game_result_serialization() {
+        let mut manager = GameStateManager::new();
+        assert!(manager.agree_draw().is_ok());
+
+        let with_history = crate::game_with_history::GameStateWithHistory::new(
+            manager.state.clone(),
+            manager.history.clone(),
+        );
+        let json = serde_json::to_value(&with_history).unwrap();
+        assert_eq!(json["game_state"]["result"]["termination"], "AgreedDraw");
+        assert!(json["game_state"]["result"]["winner"].is_null());
+
+        let restored: crate::game_with_history::GameStateWithHistory =
+            serde_json::from_value(json).unwrap();
+        assert_eq!(restored, with_history);
+        assert!(restored.game_state.result.unwrap().is_draw());
+    }
+
+    #[test]
+    fn test_from_position_rejects_impossible_placements() {
+        let start = GameState::new();
+        let state = GameState::from_position(start.board.clone(), Color::Red).unwrap();
+        assert_eq!(state, start);
+
+        // 黑象过河到红方半场
+        let mut board = start.board.clone();
+        board.set_piece(2, 0, None);
+        board.set_piece(2, 5, Some(Piece::new(PieceType::Elephant, Color::Black)));
+        assert!(matches!(
+            GameState::from_position(board, Color::Red),
+            Err(crate::ChessError::InvalidPosition(_))
+        ));
+    }
+
+    #[test]
+    fn test_perpetual_check_is_adjudicated() {
+        println!("=== 测试长将判负 ===\n");
+
+        let mut board = Board::new();
+        board.set_piece(3, 9, Some(Piece::new(PieceType::General, Color::Red)));
+        board.set_piece(4, 0, Some(Piece::new(PieceType::General, Color::Black)));
+        board.set_piece(0, 5, Some(Piece::new(PieceType::Chariot, Color::Red)));
+
+        let mut manager = GameStateManager::from_state(GameState::from_board(board, Color::Red))
+            .with_rule_set(RuleSet::Chinese);
+
+        assert!(manager.make_move(0, 5, 4, 5).is_ok());
+        for round in 0..2 {
+            println!("第 {} 轮循环", round + 1);
+            assert!(!manager.state.is_ended);
+            assert!(manager.make_move(4, 0, 5, 0).is_ok());
+            assert!(manager.make_move(4, 5, 5, 5).is_ok());
+            assert!(manager.make_move(5, 0, 4, 0).is_ok());
+            assert!(manager.make_move(5, 5, 4, 5).is_ok());
+        }
+
+        assert_eq!(
+            manager.state.result,
+            Some(GameResult::win(Color::Black, Termination::PerpetualCheck))
+        );
+        assert_eq!(manager.positions.len(), 10);
+
+        // 撤销后局面不再重复三次，对局继续
+        assert!(manager.undo_move().is_ok());
+        assert_eq!(manager.positions.len(), 9);
+        assert_eq!(manager.state.result, None);
+        assert!(!manager.state.is_ended);
+
+        println!("\n=== 测试成功！长将被正确判负 ===\n");
+    }
+
+    #[test]
+    fn test_threefold_repetition_is_a_draw() {
+        println!("=== 测试三次重复局面作和 ===\n");
+
+        let mut manager = GameStateManager::new();
+
+        for round in 0..2 {
+            println!("第 {} 轮：双方车来回走", round + 1);
+            assert!(manager.make_move(0, 9, 0, 8).is_ok());
+            assert!(manager.make_move(0, 0, 0, 1).is_ok());
+            assert!(manager.make_move(0, 8, 0, 9).is_ok());
+            assert!(!manager.state.is_ended);
+            assert!(manager.make_move(0, 1, 0, 0).is_ok());
+        }
+
+        assert_eq!(
+            manager.state.result,
+            Some(GameResult::draw(Termination::Repetition))
+        );
+        assert!(manager.state.is_ended);
+        assert_eq!(manager.state.winner, None);
+
+        // 撤销最后一步后重复表同步回退
+        assert!(manager.undo_move().is_ok());
+        assert_eq!(manager.positions.len(), 8);
+        assert_eq!(manager.state.result, None);
+
+        println!("\n=== 测试成功！三次重复局面作和 ===\n");
+    }
+
+    #[test]
+    fn test_no_capture_limit_draw() {
+        println!("=== 测试自然限着作和 ===\n");
+
+        let mut manager = GameStateManager::new().with_no_capture_limit(Some(2));
+
+        assert!(manager.make_move(1, 7, 1, 0).is_ok()); // 红炮打马
+        assert_eq!(manager.state.no_capture_plies, 0);
+        assert!(manager.make_move(0, 0, 1, 0).is_ok()); // 黑车吃炮
+        assert_eq!(manager.state.no_capture_plies, 0);
+
+        assert!(manager.make_move(0, 6, 0, 5).is_ok());
+        assert!(manager.make_move(0, 3, 0, 4).is_ok());
+        assert!(manager.make_move(2, 6, 2, 5).is_ok());
+        assert_eq!(manager.state.no_capture_plies, 3);
+        assert!(!manager.state.is_ended);
+
+        assert!(manager.make_move(2, 3, 2, 4).is_ok());
+        assert_eq!(manager.state.no_capture_plies, 4);
+        assert_eq!(
+            manager.state.result,
+            Some(GameResult::draw(Termination::MoveLimit))
+        );
+
+        // 撤销后计数器恢复
+        assert!(manager.undo_move().is_ok());
+        assert_eq!(manager.state.no_capture_plies, 3);
+        assert!(!manager.state.is_ended);
+        for _ in 0..3 {
+            assert!(manager.undo_move().is_ok());
+        }
+        assert_eq!(manager.state.no_capture_plies, 0);
+        assert!(manager.undo_move().is_ok());
+        assert_eq!(manager.state.no_capture_plies, 0);
+        assert!(manager.undo_move().is_ok());
+        assert_eq!(manager.state.no_capture_plies, 0);
+        assert_eq!(manager.positions.len(), 1);
+
+        println!("\n=== 测试成功！自然限着作和 ===\n");
+    }
+
+    #[test]
+    fn test_insufficient_material_draw() {
+        println!("=== 测试无攻击子力作和 ===\n");
+
+        // 黑将吃掉红方最后一个车后，双方都只剩防守子力
+        let mut board = Board::new();
+        board.set_piece(3, 9, Some(Piece::new(PieceType::General, Color::Red)));
+        board.set_piece(5, 9, Some(Piece::new(PieceType::Advisor, Color::Red)));
+        board.set_piece(4, 0, Some(Piece::new(PieceType::General, Color::Black)));
+        board.set_piece(2, 0, Some(Piece::new(PieceType::Elephant, Color::Black)));
+        board.set_piece(4, 1, Some(Piece::new(PieceType::Chariot, Color::Red)));
+
+        let mut manager = GameStateManager::from_state(GameState::from_board(board, Color::Black));
+
+        assert!(manager.make_move(4, 0, 4, 1).is_ok());
+        assert_eq!(
+            manager.state.result,
+            Some(GameResult::draw(Termination::InsufficientMaterial))
+        );
+
+        assert!(manager.undo_move().is_ok());
+        assert_eq!(manager.state.result, None);
+
+        println!("\n=== 测试成功！无攻击子力作和 ===\n");
+    }
+
+    #[test]
+    fn test_zobrist_key_tracks_moves_and_undo() {
+        println!("=== 测试 Zobrist 哈希增量更新 ===\n");
+
+        let mut manager = GameStateManager::new();
+        let start_key = manager.state.zobrist_key();
+        assert_eq!(
+            manager.state.board.hash(),
+            manager.state.board.compute_hash()
+        );
+
+        // 包含吃子的走法序列
+        let moves = [(1, 7, 1, 0), (0, 0, 1, 0), (7, 7, 4, 7), (1, 2, 4, 2)];
+        let mut keys = vec![start_key];
+        for (from_x, from_y, to_x, to_y) in moves {
+            assert!(manager.make_move(from_x, from_y, to_x, to_y).is_ok());
+            assert_eq!(
+                manager.state.board.hash(),
+                manager.state.board.compute_hash()
+            );
+            assert!(!keys.contains(&manager.state.zobrist_key()));
+            keys.push(manager.state.zobrist_key());
+        }
+
+        // 撤销后每一步的哈希都与之前一致
+        keys.pop();
+        while let Some(expected) = keys.pop() {
+            assert!(manager.undo_move().is_ok());
+            assert_eq!(manager.state.zobrist_key(), expected);
+            assert_eq!(
+                manager.state.board.hash(),
+                manager.state.board.compute_hash()
+            );
+        }
+        assert_eq!(manager.state.zobrist_key(), start_key);
+
+        // 同一布局不同行棋方的哈希不同
+        let mut black_to_move = manager.state.clone();
+        black_to_move.current_turn = Color::Black;
+        assert_ne!(black_to_move.zobrist_key(), start_key);
+
+        println!("\n=== 测试成功！哈希始终与重新计算结果一致 ===\n");
+    }
 }
@@ -1,3 +1,5 @@
+use crate::annotation::Annotation;
+use crate::moves::Move;
 use crate::piece::{Color, Piece, PieceType};
 use serde::{Deserialize, Serialize};
 
@@ -9,6 +11,29 @@ pub struct MoveRecord {
     pub to_y: usize,
     pub piece: Piece,
     pub captured_piece: Option<Piece>,
+    #[serde(default)]
+    pub annotation: Annotation,
+}
+
+impl MoveRecord {
+    pub fn new(mv: Move, piece: Piece, captured_piece: Option<Piece>) -> Self {
+        let (from_x, from_y, to_x, to_y) = mv.to_coords();
+        Self {
+            from_x,
+            from_y,
+            to_x,
+            to_y,
+            piece,
+            captured_piece,
+            annotation: Annotation::default(),
+        }
+    }
+
+    /// The recorded move as a typed `Move`.
+    pub fn mv(&self) -> Move {
+        Move::from_coords(self.from_x, self.from_y, self.to_x, self.to_y)
+            .expect("move records hold on-board squares")
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -63,6 +88,7 @@ impl History {
                         to_y: 0,
                         piece: Piece::new(PieceType::General, Color::Red),
                         captured_piece: None,
+                        annotation: Annotation::default(),
                     },
                     black_move: Some(move_record),
                 };
@@ -93,6 +119,16 @@ impl History {
         })
     }
 
+    /// Iterates over the recorded moves in the order they were played.
+    pub fn moves(&self) -> impl Iterator<Item = &MoveRecord> + '_ {
+        self.rounds.iter().flat_map(|round| {
+            // The red move of a round opened by Black is a placeholder with from == to
+            let red_move = Some(&round.red_move)
+                .filter(|record| (record.from_x, record.from_y) != (record.to_x, record.to_y));
+            red_move.into_iter().chain(round.black_move.as_ref())
+        })
+    }
+
     pub fn is_empty(&self) -> bool {
         self.rounds.is_empty()
     }
@@ -138,6 +174,7 @@ mod tests {
             to_y: 1,
             piece: Piece::new(PieceType::Chariot, Color::Red),
             captured_piece: None,
+            annotation: Annotation::default(),
         };
 
         let black_move = Some(MoveRecord {
@@ -147,6 +184,7 @@ mod tests {
             to_y: 8,
             piece: Piece::new(PieceType::Chariot, Color::Black),
             captured_piece: None,
+            annotation: Annotation::default(),
         });
 
         // This should fail because RoundRecord doesn't exist yet
@@ -190,6 +228,7 @@ mod tests {
             to_y: 1,
             piece: Piece::new(PieceType::Chariot, Color::Red),
             captured_piece: None,
+            annotation: Annotation::default(),
         };
 
         history.push_with_color(move_record.clone(), Color::Red);
@@ -218,6 +257,7 @@ mod tests {
             to_y: 4,
             piece: Piece::new(PieceType::Chariot, Color::Red),
             captured_piece: None,
+            annotation: Annotation::default(),
         };
 
         history.push_with_color(move_record.clone(), Color::Red);
@@ -242,6 +282,7 @@ mod tests {
             to_y: 1,
             piece: Piece::new(PieceType::Chariot, Color::Red),
             captured_piece: None,
+            annotation: Annotation::default(),
         };
 
         history.push_with_color(move_record.clone(), Color::Red);
@@ -266,6 +307,7 @@ mod tests {
             to_y: 1,
             piece: Piece::new(PieceType::Chariot, Color::Red),
             captured_piece: None,
+            annotation: Annotation::default(),
         };
 
         history.push_with_color(move_record.clone(), Color::Red);
@@ -289,6 +331,7 @@ mod tests {
                 to_y: 1,
                 piece: Piece::new(PieceType::Chariot, Color::Red),
                 captured_piece: None,
+                annotation: Annotation::default(),
             },
             black_move: Some(MoveRecord {
                 from_x: 8,
@@ -297,6 +340,7 @@ mod tests {
                 to_y: 8,
                 piece: Piece::new(PieceType::Chariot, Color::Black),
                 captured_piece: None,
+                annotation: Annotation::default(),
             }),
         };
 
@@ -317,6 +361,7 @@ mod tests {
             to_y: 1,
             piece: Piece::new(PieceType::Chariot, Color::Red),
             captured_piece: None,
+            annotation: Annotation::default(),
         };
 
         history.push_with_color(move_record.clone(), Color::Red);
@@ -339,6 +384,7 @@ mod tests {
             to_y: 1,
             piece: Piece::new(PieceType::Chariot, Color::Red),
             captured_piece: None,
+            annotation: Annotation::default(),
         };
 
         let black_move = MoveRecord {
@@ -348,6 +394,7 @@ mod tests {
             to_y: 8,
             piece: Piece::new(PieceType::Chariot, Color::Black),
             captured_piece: None,
+            annotation: Annotation::default(),
         };
 
         // Push red move - should create new round
@@ -373,6 +420,7 @@ mod tests {
             to_y: 2,
             piece: Piece::new(PieceType::Chariot, Color::Red),
             captured_piece: None,
+            annotation: Annotation::default(),
         };
         history.push_with_color(red_move2.clone(), Color::Red);
         assert_eq!(history.rounds.len(), 2);
@@ -393,6 +441,7 @@ mod tests {
             to_y: 1,
             piece: Piece::new(PieceType::Chariot, Color::Red),
             captured_piece: None,
+            annotation: Annotation::default(),
         };
 
         let black_move = MoveRecord {
@@ -402,6 +451,7 @@ mod tests {
             to_y: 8,
             piece: Piece::new(PieceType::Chariot, Color::Black),
             captured_piece: None,
+            annotation: Annotation::default(),
         };
 
         // Push red move
@@ -441,6 +491,7 @@ mod tests {
             to_y: 1,
             piece: Piece::new(PieceType::Chariot, Color::Red),
             captured_piece: None,
+            annotation: Annotation::default(),
         };
 
         history.push_with_color(red_move.clone(), Color::Red);
@@ -468,6 +519,7 @@ mod tests {
             to_y: 8,
             piece: Piece::new(PieceType::Chariot, Color::Black),
             captured_piece: None,
+            annotation: Annotation::default(),
         };
 
         // Black move first (edge case)
@@ -486,6 +538,7 @@ mod tests {
             to_y: 1,
             piece: Piece::new(PieceType::Chariot, Color::Red),
             captured_piece: None,
+            annotation: Annotation::default(),
         };
         history.push_with_color(red_move.clone(), Color::Red);
         assert_eq!(history.len(), 2);
@@ -0,0 +1,103 @@
+use crate::moves::Move;
+use crate::square::Square;
+use crate::ChessError;
+
+// ICCS names files a-i from Red's left and ranks 0-9 from Red's back rank,
+// so file = x and rank = 9 - y.
+
+/// Parses a square such as "h2".
+pub fn parse_square(text: &str) -> Result<Square, ChessError> {
+    let mut chars = text.chars();
+    let (file, rank) = match (chars.next(), chars.next(), chars.next()) {
+        (Some(file), Some(rank), None) => (file.to_ascii_lowercase(), rank),
+        _ => return Err(invalid(text)),
+    };
+    if !('a'..='i').contains(&file) {
+        return Err(invalid(text));
+    }
+    let rank = rank.to_digit(10).ok_or_else(|| invalid(text))? as usize;
+    Square::from_xy(file as usize - 'a' as usize, 9 - rank).ok_or_else(|| invalid(text))
+}
+
+/// Formats a square such as "h2".
+pub fn format_square(square: Square) -> String {
+    let file = (b'a' + square.x() as u8) as char;
+    format!("{}{}", file, 9 - square.y())
+}
+
+/// Parses a move such as "h2e2". The upper case "H2-E2" form is accepted too.
+pub fn parse_move(text: &str) -> Result<Move, ChessError> {
+    let text = text.trim();
+    let (from, to) = match text.split_once('-') {
+        Some(squares) => squares,
+        None if text.is_ascii() && text.len() == 4 => text.split_at(2),
+        None => return Err(invalid(text)),
+    };
+    Ok(Move::new(parse_square(from)?, parse_square(to)?))
+}
+
+/// Formats a move such as "h2e2".
+pub fn format_move(mv: Move) -> String {
+    format!("{}{}", format_square(mv.from()), format_square(mv.to()))
+}
+
+fn invalid(text: &str) -> ChessError {
+    ChessError::InvalidNotation(format!("'{}' is not an ICCS move", text))
+}
+
+impl Move {
+    /// Parses a move in ICCS notation.
+    pub fn from_iccs(text: &str) -> Result<Self, ChessError> {
+        parse_move(text)
+    }
+
+    /// Formats the move in ICCS notation.
+    pub fn to_iccs(self) -> String {
+        format_move(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_orientation() {
+        // Red's right cannon to the centre file
+        let mv = parse_move("h2e2").unwrap();
+        assert_eq!(mv.to_coords(), (7, 7, 4, 7));
+        assert_eq!(format_move(mv), "h2e2");
+
+        // Black's left horse, on Red's right side of the top rank
+        let mv = Move::from_iccs("h9g7").unwrap();
+        assert_eq!(mv.to_coords(), (7, 0, 6, 2));
+        assert_eq!(mv.to_iccs(), "h9g7");
+
+        assert_eq!(parse_square("a0").unwrap().to_xy(), (0, 9));
+        assert_eq!(parse_square("i9").unwrap().to_xy(), (8, 0));
+    }
+
+    #[test]
+    fn test_alternative_forms() {
+        assert_eq!(parse_move("H2-E2"), parse_move("h2e2"));
+        assert_eq!(parse_move(" b0c2 "), parse_move("b0-c2"));
+    }
+
+    #[test]
+    fn test_every_square_round_trips() {
+        for square in Square::all() {
+            assert_eq!(parse_square(&format_square(square)), Ok(square));
+        }
+    }
+
+    #[test]
+    fn test_invalid_moves() {
+        for text in ["", "h2e", "h2e2e", "j2e2", "h2ex", "h2-", "炮二平五"] {
+            assert!(
+                matches!(parse_move(text), Err(ChessError::InvalidNotation(_))),
+                "{}",
+                text
+            );
+        }
+    }
+}
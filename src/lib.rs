@@ -1,11 +1,27 @@
+pub mod annotation;
+pub mod attack;
 pub mod board;
+pub mod dhtmlxq;
+pub mod fen;
 pub mod game;
 pub mod game_with_history;
 pub mod history;
+pub mod iccs;
+pub mod material;
+pub mod movegen;
+pub mod moves;
+pub mod notation;
+pub mod perft;
+pub mod pgn;
 pub mod piece;
+pub mod repetition;
 pub mod rules;
+pub mod square;
 pub mod tauri_commands;
+pub mod tree;
 pub mod validator;
+pub mod xqf;
+pub mod zobrist;
 
 use serde::{Deserialize, Serialize};
 
@@ -17,7 +33,16 @@ pub enum ChessError {
     CannotCaptureOwnPiece,
     InCheck,
     NoHistory,
+    NothingToRedo,
     GameEnded,
+    GeneralsFacing,
+    UnknownNode,
+    InvalidPosition(String),
+    InvalidFen(String),
+    InvalidNotation(String),
+    InvalidPgn(String),
+    InvalidXqf(String),
+    InvalidDhtmlXq(String),
 }
 
 impl std::fmt::Display for ChessError {
@@ -29,7 +54,16 @@ impl std::fmt::Display for ChessError {
             ChessError::CannotCaptureOwnPiece => write!(f, "Cannot capture your own piece"),
             ChessError::InCheck => write!(f, "You are in check"),
             ChessError::NoHistory => write!(f, "No move history"),
+            ChessError::NothingToRedo => write!(f, "No move to redo"),
             ChessError::GameEnded => write!(f, "Game has ended"),
+            ChessError::GeneralsFacing => write!(f, "Generals cannot face each other"),
+            ChessError::UnknownNode => write!(f, "No such move in the game tree"),
+            ChessError::InvalidPosition(reason) => write!(f, "Invalid position: {}", reason),
+            ChessError::InvalidFen(reason) => write!(f, "Invalid FEN: {}", reason),
+            ChessError::InvalidNotation(reason) => write!(f, "Invalid notation: {}", reason),
+            ChessError::InvalidPgn(reason) => write!(f, "Invalid PGN: {}", reason),
+            ChessError::InvalidXqf(reason) => write!(f, "Invalid XQF: {}", reason),
+            ChessError::InvalidDhtmlXq(reason) => write!(f, "Invalid DhtmlXQ: {}", reason),
         }
     }
 }
@@ -11,10 +11,36 @@ fn main() {
         })
         .invoke_handler(tauri::generate_handler![
             chinese_chess::tauri_commands::make_move,
+            chinese_chess::tauri_commands::make_move_iccs,
             chinese_chess::tauri_commands::undo_move,
+            chinese_chess::tauri_commands::redo_move,
             chinese_chess::tauri_commands::get_valid_moves,
+            chinese_chess::tauri_commands::get_attackers,
+            chinese_chess::tauri_commands::get_checkers,
+            chinese_chess::tauri_commands::get_move_list,
             chinese_chess::tauri_commands::get_game_state,
-            chinese_chess::tauri_commands::new_game
+            chinese_chess::tauri_commands::new_game,
+            chinese_chess::tauri_commands::get_rule_set,
+            chinese_chess::tauri_commands::set_rule_set,
+            chinese_chess::tauri_commands::get_fen,
+            chinese_chess::tauri_commands::load_fen,
+            chinese_chess::tauri_commands::export_pgn,
+            chinese_chess::tauri_commands::load_pgn,
+            chinese_chess::tauri_commands::load_xqf,
+            chinese_chess::tauri_commands::export_dhtmlxq,
+            chinese_chess::tauri_commands::load_dhtmlxq,
+            chinese_chess::tauri_commands::get_game_tree,
+            chinese_chess::tauri_commands::get_current_node,
+            chinese_chess::tauri_commands::goto_node,
+            chinese_chess::tauri_commands::promote_variation,
+            chinese_chess::tauri_commands::demote_variation,
+            chinese_chess::tauri_commands::goto_ply,
+            chinese_chess::tauri_commands::get_replay_position,
+            chinese_chess::tauri_commands::goto_first,
+            chinese_chess::tauri_commands::goto_last,
+            chinese_chess::tauri_commands::goto_previous,
+            chinese_chess::tauri_commands::goto_next,
+            chinese_chess::tauri_commands::annotate_move
         ]);
 
     app.run(tauri::generate_context!())
@@ -0,0 +1,118 @@
+use crate::board::Board;
+use crate::piece::{Color, PieceType};
+
+/// Returns true when the color still has a piece able to cross the river and
+/// attack: a chariot, horse, cannon or soldier. Generals, advisors and elephants
+/// never leave their own half of the board.
+pub fn has_attacking_material(board: &Board, color: Color) -> bool {
+    for y in 0..10 {
+        for x in 0..9 {
+            if let Some(piece) = board.get_piece(x, y) {
+                if piece.color == color && is_attacking(piece.piece_type) {
+                    return true;
+                }
+            }
+        }
+    }
+    false
+}
+
+/// Returns true when neither side can ever deliver mate.
+pub fn is_insufficient_material(board: &Board) -> bool {
+    !has_attacking_material(board, Color::Red) && !has_attacking_material(board, Color::Black)
+}
+
+fn is_attacking(piece_type: PieceType) -> bool {
+    match piece_type {
+        PieceType::Chariot | PieceType::Horse | PieceType::Cannon | PieceType::Soldier => true,
+        PieceType::General | PieceType::Advisor | PieceType::Elephant => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::piece::Piece;
+
+    /// Builds a board from a material signature such as "KAA/KEE", red first.
+    /// Letters follow FEN: K general, A advisor, E elephant, H horse, R chariot,
+    /// C cannon, P soldier.
+    fn board_from_signature(signature: &str) -> Board {
+        let mut board = Board::new();
+        let (red, black) = signature.split_once('/').unwrap();
+
+        for (pieces, color) in [(red, Color::Red), (black, Color::Black)] {
+            // Squares on the owner's half, as (file, rank from the owner's back rank)
+            let mut advisors = vec![(3, 0), (5, 0)].into_iter();
+            let mut elephants = vec![(2, 0), (6, 0)].into_iter();
+            let mut others = (0..9).map(|x| (x, 3));
+
+            for letter in pieces.chars() {
+                let (piece_type, (x, rank)) = match letter {
+                    'K' => (PieceType::General, (4, 0)),
+                    'A' => (PieceType::Advisor, advisors.next().unwrap()),
+                    'E' => (PieceType::Elephant, elephants.next().unwrap()),
+                    'H' => (PieceType::Horse, others.next().unwrap()),
+                    'R' => (PieceType::Chariot, others.next().unwrap()),
+                    'C' => (PieceType::Cannon, others.next().unwrap()),
+                    'P' => (PieceType::Soldier, others.next().unwrap()),
+                    _ => panic!("unknown piece letter {}", letter),
+                };
+                let y = match color {
+                    Color::Red => 9 - rank,
+                    Color::Black => rank,
+                };
+                board.set_piece(x, y, Some(Piece::new(piece_type, color)));
+            }
+        }
+
+        board
+    }
+
+    #[test]
+    fn test_dead_draw_signatures() {
+        let dead_draws = [
+            "K/K",
+            "KA/K",
+            "KE/K",
+            "KAA/K",
+            "KEE/K",
+            "KAAEE/K",
+            "KAA/KEE",
+            "KAE/KAE",
+            "KAAEE/KAAEE",
+        ];
+
+        for signature in dead_draws {
+            let board = board_from_signature(signature);
+            assert!(
+                is_insufficient_material(&board),
+                "{} should be a dead draw",
+                signature
+            );
+        }
+    }
+
+    #[test]
+    fn test_signatures_with_attacking_material() {
+        let playable = [
+            ("KR/K", Color::Red),
+            ("KH/KAAEE", Color::Red),
+            ("KC/KAA", Color::Red),
+            ("KP/KEE", Color::Red),
+            ("KAAEE/KP", Color::Black),
+            ("KAA/KHC", Color::Black),
+        ];
+
+        for (signature, attacker) in playable {
+            let board = board_from_signature(signature);
+            assert!(
+                !is_insufficient_material(&board),
+                "{} should not be a dead draw",
+                signature
+            );
+            assert!(has_attacking_material(&board, attacker));
+            assert!(!has_attacking_material(&board, attacker.opponent()));
+        }
+    }
+}
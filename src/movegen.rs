@@ -0,0 +1,327 @@
+use crate::attack;
+use crate::board::Board;
+use crate::moves::Move;
+use crate::piece::{Color, Piece, PieceType};
+use crate::rules;
+use crate::square::Square;
+
+pub(crate) const ORTHOGONAL: [(isize, isize); 4] = [(0, -1), (0, 1), (-1, 0), (1, 0)];
+pub(crate) const DIAGONAL: [(isize, isize); 4] = [(-1, -1), (1, -1), (-1, 1), (1, 1)];
+
+// Horse jumps as (delta, leg), the leg being the square next to the horse that blocks it
+pub(crate) const HORSE_JUMPS: [((isize, isize), (isize, isize)); 8] = [
+    ((1, -2), (0, -1)),
+    ((-1, -2), (0, -1)),
+    ((1, 2), (0, 1)),
+    ((-1, 2), (0, 1)),
+    ((2, 1), (1, 0)),
+    ((2, -1), (1, 0)),
+    ((-2, 1), (-1, 0)),
+    ((-2, -1), (-1, 0)),
+];
+
+/// Generates the target squares of the piece on `from` following its movement
+/// rules, without checking whether the move leaves its own general in check.
+pub fn pseudo_legal_targets(board: &Board, from: Square) -> Vec<Square> {
+    let mut targets = Vec::new();
+    if let Some(piece) = board.piece_at(from) {
+        push_targets(board, from, piece, &mut targets);
+    }
+    targets
+}
+
+/// Generates the legal target squares of the piece on `from` for the side to
+/// move. Each candidate is made and taken back on `board`, which is left
+/// unchanged.
+pub fn legal_targets(board: &mut Board, from: Square, color: Color) -> Vec<Square> {
+    match board.piece_at(from) {
+        Some(piece) if piece.color == color => pseudo_legal_targets(board, from)
+            .into_iter()
+            .filter(|&to| is_legal(board, Move::new(from, to), color))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Generates every pseudo-legal move of the given color.
+pub fn pseudo_legal_moves(board: &Board, color: Color) -> Vec<Move> {
+    let mut moves = Vec::new();
+    for from in pieces_of(board, color) {
+        for to in pseudo_legal_targets(board, from) {
+            moves.push(Move::new(from, to));
+        }
+    }
+    moves
+}
+
+/// Generates every legal move of the given color. `board` is left unchanged.
+pub fn legal_moves(board: &mut Board, color: Color) -> Vec<Move> {
+    pseudo_legal_moves(board, color)
+        .into_iter()
+        .filter(|&mv| is_legal(board, mv, color))
+        .collect()
+}
+
+/// Returns true when the given color has at least one legal move. `board` is
+/// left unchanged.
+pub fn has_legal_move(board: &mut Board, color: Color) -> bool {
+    for from in pieces_of(board, color) {
+        for to in pseudo_legal_targets(board, from) {
+            if is_legal(board, Move::new(from, to), color) {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+/// Returns true when a pseudo-legal move leaves the mover's general safe. The
+/// move is made and taken back on `board`, which is left unchanged. The enemy
+/// general attacking along an open file covers the flying-general rule.
+pub fn is_legal(board: &mut Board, mv: Move, color: Color) -> bool {
+    let general = match board.piece_at(mv.from()) {
+        Some(piece) if piece.piece_type == PieceType::General => Some(mv.to()),
+        _ => rules::find_general(board, color),
+    };
+    let undo = board.make_move(mv);
+    let legal = match general {
+        Some(general) => !attack::is_square_attacked(board, general, color.opponent()),
+        None => true,
+    };
+    board.unmake_move(undo);
+    legal
+}
+
+fn push_targets(board: &Board, from: Square, piece: Piece, targets: &mut Vec<Square>) {
+    let color = piece.color;
+    let push = |to: Square, targets: &mut Vec<Square>| {
+        if !matches!(board.piece_at(to), Some(other) if other.color == color) {
+            targets.push(to);
+        }
+    };
+    let is_legal_square = |to: Square| rules::is_legal_square(piece, to.x(), to.y());
+
+    match piece.piece_type {
+        PieceType::General => {
+            for (dx, dy) in ORTHOGONAL {
+                if let Some(to) = from.offset(dx, dy) {
+                    if is_legal_square(to) {
+                        push(to, targets);
+                    }
+                }
+            }
+            // Flying capture of the enemy general along an open file
+            for dy in [-1, 1] {
+                if let Some(to) = first_piece(board, from, 0, dy) {
+                    if board.piece_at(to) == Some(Piece::new(PieceType::General, color.opponent()))
+                    {
+                        targets.push(to);
+                    }
+                }
+            }
+        }
+        PieceType::Advisor => {
+            for (dx, dy) in DIAGONAL {
+                if let Some(to) = from.offset(dx, dy) {
+                    if is_legal_square(to) {
+                        push(to, targets);
+                    }
+                }
+            }
+        }
+        PieceType::Elephant => {
+            for (dx, dy) in DIAGONAL {
+                if let (Some(eye), Some(to)) = (from.offset(dx, dy), from.offset(dx * 2, dy * 2)) {
+                    if board.piece_at(eye).is_none() && is_legal_square(to) {
+                        push(to, targets);
+                    }
+                }
+            }
+        }
+        PieceType::Horse => {
+            for ((dx, dy), (leg_x, leg_y)) in HORSE_JUMPS {
+                if let (Some(leg), Some(to)) = (from.offset(leg_x, leg_y), from.offset(dx, dy)) {
+                    if board.piece_at(leg).is_none() {
+                        push(to, targets);
+                    }
+                }
+            }
+        }
+        PieceType::Chariot => {
+            for (dx, dy) in ORTHOGONAL {
+                let mut pos = from.offset(dx, dy);
+                while let Some(to) = pos {
+                    push(to, targets);
+                    if board.piece_at(to).is_some() {
+                        break;
+                    }
+                    pos = to.offset(dx, dy);
+                }
+            }
+        }
+        PieceType::Cannon => {
+            for (dx, dy) in ORTHOGONAL {
+                let mut pos = from.offset(dx, dy);
+                while let Some(to) = pos {
+                    if board.piece_at(to).is_some() {
+                        // Jump over the screen to capture the next piece
+                        if let Some(target) = first_piece(board, to, dx, dy) {
+                            push(target, targets);
+                        }
+                        break;
+                    }
+                    targets.push(to);
+                    pos = to.offset(dx, dy);
+                }
+            }
+        }
+        PieceType::Soldier => {
+            if let Some(to) = from.offset(0, forward_dy(color)) {
+                push(to, targets);
+            }
+            if has_crossed_river(from.y(), color) {
+                for dx in [-1, 1] {
+                    if let Some(to) = from.offset(dx, 0) {
+                        push(to, targets);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Finds the first occupied square beyond `from` in the given direction.
+pub(crate) fn first_piece(board: &Board, from: Square, dx: isize, dy: isize) -> Option<Square> {
+    let mut pos = from.offset(dx, dy);
+    while let Some(square) = pos {
+        if board.piece_at(square).is_some() {
+            return Some(square);
+        }
+        pos = square.offset(dx, dy);
+    }
+    None
+}
+
+// Red moves up the board (y decreases), Black moves down
+pub(crate) fn forward_dy(color: Color) -> isize {
+    match color {
+        Color::Red => -1,
+        Color::Black => 1,
+    }
+}
+
+pub(crate) fn has_crossed_river(y: usize, color: Color) -> bool {
+    match color {
+        Color::Red => y <= 4,
+        Color::Black => y >= 5,
+    }
+}
+
+fn pieces_of(board: &Board, color: Color) -> Vec<Square> {
+    Square::all()
+        .filter(|&square| matches!(board.piece_at(square), Some(piece) if piece.color == color))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::GameState;
+
+    fn sq(x: usize, y: usize) -> Square {
+        Square::from_xy(x, y).unwrap()
+    }
+
+    // Reference implementation: try every square with the rule validator and
+    // look for the general being attacked by scanning every enemy piece
+    fn scan_legal_moves(board: &mut Board, color: Color) -> Vec<Move> {
+        let mut moves = Vec::new();
+        for from in Square::all() {
+            for to in Square::all() {
+                let mv = Move::new(from, to);
+                if rules::validate_move(board, mv, color).is_err() {
+                    continue;
+                }
+                let undo = board.make_move(mv);
+                if !scan_in_check(board, color) {
+                    moves.push(mv);
+                }
+                board.unmake_move(undo);
+            }
+        }
+        moves
+    }
+
+    fn scan_in_check(board: &mut Board, color: Color) -> bool {
+        match rules::find_general(board, color) {
+            Some(general) => Square::all().any(|from| {
+                rules::validate_move(board, Move::new(from, general), color.opponent()).is_ok()
+            }),
+            None => false,
+        }
+    }
+
+    fn sorted(mut moves: Vec<Move>) -> Vec<Move> {
+        moves.sort();
+        moves
+    }
+
+    #[test]
+    fn test_start_position_move_count() {
+        let mut state = GameState::new();
+        assert_eq!(legal_moves(&mut state.board, Color::Red).len(), 44);
+        assert_eq!(legal_moves(&mut state.board, Color::Black).len(), 44);
+    }
+
+    #[test]
+    fn test_generator_matches_validator_scan() {
+        // Walk a deterministic pseudo-random game and compare both generators
+        // at every ply
+        let mut board = GameState::new().board;
+        let mut color = Color::Red;
+        let mut seed: u64 = 0x1234_5678;
+
+        for _ in 0..120 {
+            let generated = sorted(legal_moves(&mut board, color));
+            assert_eq!(generated, sorted(scan_legal_moves(&mut board, color)));
+            assert_eq!(has_legal_move(&mut board, color), !generated.is_empty());
+
+            if generated.is_empty() {
+                break;
+            }
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            let mv = generated[(seed >> 33) as usize % generated.len()];
+            let undo = board.make_move(mv);
+            if matches!(undo.captured, Some(piece) if piece.piece_type == PieceType::General) {
+                break;
+            }
+            color = color.opponent();
+        }
+    }
+
+    #[test]
+    fn test_cannon_and_horse_targets() {
+        let mut board = Board::new();
+        board.set_piece(4, 7, Some(Piece::new(PieceType::Cannon, Color::Red)));
+        board.set_piece(4, 3, Some(Piece::new(PieceType::Soldier, Color::Black)));
+        board.set_piece(4, 0, Some(Piece::new(PieceType::General, Color::Black)));
+        board.set_piece(4, 8, Some(Piece::new(PieceType::Horse, Color::Red)));
+
+        let cannon = pseudo_legal_targets(&board, sq(4, 7));
+        // Quiet moves up to the screen, then the capture beyond it
+        assert!(cannon.contains(&sq(4, 4)));
+        assert!(!cannon.contains(&sq(4, 3)));
+        assert!(cannon.contains(&sq(4, 0)));
+        assert!(!cannon.contains(&sq(4, 8)));
+        assert!(attack::is_square_attacked(&board, sq(4, 0), Color::Red));
+
+        // The cannon blocks the horse's leg towards the top
+        let horse = pseudo_legal_targets(&board, sq(4, 8));
+        assert!(!horse.contains(&sq(3, 6)));
+        assert!(!horse.contains(&sq(5, 6)));
+        assert!(horse.contains(&sq(2, 7)));
+        assert!(horse.contains(&sq(6, 9)));
+    }
+}
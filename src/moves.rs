@@ -0,0 +1,71 @@
+use crate::square::Square;
+use crate::ChessError;
+
+/// A move packed into 16 bits: the from square in the high byte and the to
+/// square in the low byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Move(u16);
+
+impl Move {
+    pub fn new(from: Square, to: Square) -> Self {
+        Self(((from.index() as u16) << 8) | to.index() as u16)
+    }
+
+    /// Creates a move from (x, y) coordinates, rejecting squares off the board.
+    pub fn from_coords(
+        from_x: usize,
+        from_y: usize,
+        to_x: usize,
+        to_y: usize,
+    ) -> Result<Self, ChessError> {
+        Ok(Self::new(
+            Square::try_from((from_x, from_y))?,
+            Square::try_from((to_x, to_y))?,
+        ))
+    }
+
+    /// Unpacks a raw value, or None when either half is not a square.
+    pub fn from_raw(raw: u16) -> Option<Self> {
+        let from = Square::new((raw >> 8) as usize)?;
+        let to = Square::new((raw & 0xff) as usize)?;
+        Some(Self::new(from, to))
+    }
+
+    pub fn raw(self) -> u16 {
+        self.0
+    }
+
+    pub fn from(self) -> Square {
+        Square::new((self.0 >> 8) as usize).unwrap()
+    }
+
+    pub fn to(self) -> Square {
+        Square::new((self.0 & 0xff) as usize).unwrap()
+    }
+
+    /// Returns the move as (from_x, from_y, to_x, to_y).
+    pub fn to_coords(self) -> (usize, usize, usize, usize) {
+        let (from_x, from_y) = self.from().to_xy();
+        let (to_x, to_y) = self.to().to_xy();
+        (from_x, from_y, to_x, to_y)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_move_packing() {
+        let mv = Move::from_coords(1, 9, 2, 7).unwrap();
+        assert_eq!(mv.from(), Square::from_xy(1, 9).unwrap());
+        assert_eq!(mv.to(), Square::from_xy(2, 7).unwrap());
+        assert_eq!(mv.to_coords(), (1, 9, 2, 7));
+        assert_eq!(Move::from_raw(mv.raw()), Some(mv));
+
+        assert_eq!(Move::from_coords(9, 0, 0, 0), Err(ChessError::OutOfBoard));
+        assert_eq!(Move::from_coords(0, 0, 0, 10), Err(ChessError::OutOfBoard));
+        assert_eq!(Move::from_raw(90 << 8), None);
+        assert_eq!(Move::from_raw(90), None);
+    }
+}
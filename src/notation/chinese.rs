@@ -0,0 +1,365 @@
+use super::{describe, Direction, MoveFormat, Origin};
+use crate::board::Board;
+use crate::game::GameStateManager;
+use crate::movegen;
+use crate::moves::Move;
+use crate::piece::{Color, Piece, PieceType};
+use crate::ChessError;
+
+// Red writes numbers in Chinese numerals, Black in Arabic ones:
+// 炮二平五 for Red, 马8进7 for Black
+const RED_NUMERALS: [char; 9] = ['一', '二', '三', '四', '五', '六', '七', '八', '九'];
+
+/// Formats a move such as 炮二平五 from the position before it is played.
+pub fn format_move(board: &Board, mv: Move) -> Result<String, ChessError> {
+    let description = describe(board, mv)?;
+    let color = description.piece.color;
+    let name = piece_name(description.piece);
+
+    let mut text = match description.origin {
+        Origin::File(file) => format!("{}{}", name, numeral(color, file)),
+        Origin::Tandem {
+            file,
+            index,
+            count,
+            shared,
+        } => {
+            // 前/后 for two pieces, 前/中/后 for three, then counting from the front
+            let position = match (count, index) {
+                (2, 0) | (3, 0) => "前".to_string(),
+                (3, 1) => "中".to_string(),
+                (2, _) | (3, _) => "后".to_string(),
+                _ => numeral(color, index + 1),
+            };
+            // With tandems on two files the file number takes the name's place
+            if shared && count <= 3 {
+                format!("{}{}", position, numeral(color, file))
+            } else {
+                format!("{}{}", position, name)
+            }
+        }
+    };
+
+    text.push(match description.direction {
+        Direction::Forward => '进',
+        Direction::Backward => '退',
+        Direction::Sideways => '平',
+    });
+    text.push_str(&numeral(color, description.amount));
+    Ok(text)
+}
+
+/// Parses a move such as 马8进7 for `color` in the given position. Traditional
+/// characters, either side's numerals and full-width digits are accepted.
+pub fn parse_move(board: &mut Board, color: Color, text: &str) -> Result<Move, ChessError> {
+    let wanted = normalize(text);
+    if !wanted.is_empty() {
+        for mv in movegen::legal_moves(board, color) {
+            if let Ok(candidate) = format_move(board, mv) {
+                if normalize(&candidate) == wanted {
+                    return Ok(mv);
+                }
+            }
+        }
+    }
+    Err(ChessError::InvalidNotation(format!(
+        "'{}' is not a legal move in Chinese notation",
+        text.trim()
+    )))
+}
+
+fn piece_name(piece: Piece) -> char {
+    match (piece.piece_type, piece.color) {
+        (PieceType::General, Color::Red) => '帅',
+        (PieceType::General, Color::Black) => '将',
+        (PieceType::Advisor, Color::Red) => '仕',
+        (PieceType::Advisor, Color::Black) => '士',
+        (PieceType::Elephant, Color::Red) => '相',
+        (PieceType::Elephant, Color::Black) => '象',
+        (PieceType::Horse, _) => '马',
+        (PieceType::Chariot, _) => '车',
+        (PieceType::Cannon, _) => '炮',
+        (PieceType::Soldier, Color::Red) => '兵',
+        (PieceType::Soldier, Color::Black) => '卒',
+    }
+}
+
+fn numeral(color: Color, n: usize) -> String {
+    match color {
+        Color::Red => RED_NUMERALS[n - 1].to_string(),
+        Color::Black => n.to_string(),
+    }
+}
+
+// Maps every spelling of a move onto one form: piece names without color,
+// simplified characters and ASCII digits. The side to move is known when
+// parsing, so nothing is lost.
+fn normalize(text: &str) -> String {
+    text.chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| match c {
+            '車' | '俥' => '车',
+            '馬' | '傌' => '马',
+            '砲' | '包' => '炮',
+            '帥' | '將' | '将' => '帅',
+            '士' => '仕',
+            '象' => '相',
+            '卒' => '兵',
+            '進' => '进',
+            '後' => '后',
+            '１'..='９' => char::from_u32(c as u32 - '１' as u32 + '1' as u32).unwrap(),
+            _ => match RED_NUMERALS.iter().position(|&numeral| numeral == c) {
+                Some(index) => (b'1' + index as u8) as char,
+                None => c,
+            },
+        })
+        .collect()
+}
+
+impl GameStateManager {
+    /// The moves played so far in Chinese notation, one entry per move.
+    pub fn chinese_moves(&self) -> Vec<String> {
+        self.notated_moves(MoveFormat::Chinese)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::GameState;
+
+    fn board(pieces: &[(usize, usize, PieceType, Color)]) -> Board {
+        let mut board = Board::new();
+        for &(x, y, piece_type, color) in pieces {
+            board.set_piece(x, y, Some(Piece::new(piece_type, color)));
+        }
+        board
+    }
+
+    fn format(board: &Board, from_x: usize, from_y: usize, to_x: usize, to_y: usize) -> String {
+        format_move(
+            board,
+            Move::from_coords(from_x, from_y, to_x, to_y).unwrap(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_opening_moves() {
+        let board = GameState::new().board;
+        assert_eq!(format(&board, 7, 7, 4, 7), "炮二平五");
+        assert_eq!(format(&board, 7, 9, 6, 7), "马二进三");
+        assert_eq!(format(&board, 8, 9, 8, 8), "车一进一");
+        assert_eq!(format(&board, 6, 9, 4, 7), "相三进五");
+        assert_eq!(format(&board, 5, 9, 4, 8), "仕四进五");
+        assert_eq!(format(&board, 4, 9, 4, 8), "帅五进一");
+        assert_eq!(format(&board, 2, 6, 2, 5), "兵七进一");
+
+        // Black counts from its own right, the left side of the board as Red sees it
+        assert_eq!(format(&board, 7, 0, 6, 2), "马8进7");
+        assert_eq!(format(&board, 1, 2, 4, 2), "炮2平5");
+        assert_eq!(format(&board, 6, 3, 6, 4), "卒7进1");
+        assert_eq!(format(&board, 2, 0, 4, 2), "象3进5");
+        assert_eq!(format(&board, 7, 2, 7, 9), "炮8进7");
+    }
+
+    #[test]
+    fn test_retreats() {
+        let board = board(&[
+            (4, 9, PieceType::General, Color::Red),
+            (3, 0, PieceType::General, Color::Black),
+            (2, 2, PieceType::Horse, Color::Red),
+            (6, 6, PieceType::Chariot, Color::Black),
+            (4, 1, PieceType::Advisor, Color::Black),
+        ]);
+        assert_eq!(format(&board, 2, 2, 3, 4), "马七退六");
+        assert_eq!(format(&board, 6, 6, 6, 1), "车7退5");
+        assert_eq!(format(&board, 4, 1, 5, 0), "士5退6");
+        assert_eq!(format(&board, 4, 9, 3, 9), "帅五平六");
+    }
+
+    #[test]
+    fn test_tandem_pieces() {
+        let board = board(&[
+            (4, 9, PieceType::General, Color::Red),
+            (3, 0, PieceType::General, Color::Black),
+            (2, 3, PieceType::Chariot, Color::Red),
+            (2, 6, PieceType::Chariot, Color::Red),
+            (1, 2, PieceType::Cannon, Color::Black),
+            (1, 5, PieceType::Cannon, Color::Black),
+            // Advisors on one file keep the plain form
+            (3, 7, PieceType::Advisor, Color::Red),
+            (3, 9, PieceType::Advisor, Color::Red),
+        ]);
+        assert_eq!(format(&board, 2, 3, 2, 2), "前车进一");
+        assert_eq!(format(&board, 2, 6, 5, 6), "后车平四");
+        // Black's front is further down the board
+        assert_eq!(format(&board, 1, 5, 1, 6), "前炮进1");
+        assert_eq!(format(&board, 1, 2, 1, 1), "后炮退1");
+        assert_eq!(format(&board, 3, 9, 4, 8), "仕六进五");
+        assert_eq!(format(&board, 3, 7, 4, 8), "仕六退五");
+    }
+
+    #[test]
+    fn test_soldiers_on_one_file() {
+        let mut three = board(&[
+            (3, 9, PieceType::General, Color::Red),
+            (5, 0, PieceType::General, Color::Black),
+            (4, 2, PieceType::Soldier, Color::Red),
+            (4, 3, PieceType::Soldier, Color::Red),
+            (4, 4, PieceType::Soldier, Color::Red),
+        ]);
+        assert_eq!(format(&three, 4, 2, 4, 1), "前兵进一");
+        assert_eq!(format(&three, 4, 3, 3, 3), "中兵平六");
+        assert_eq!(format(&three, 4, 4, 5, 4), "后兵平四");
+
+        // Four or more are numbered from the front
+        three.set_piece(4, 1, Some(Piece::new(PieceType::Soldier, Color::Red)));
+        assert_eq!(format(&three, 4, 1, 3, 1), "一兵平六");
+        assert_eq!(format(&three, 4, 3, 5, 3), "三兵平四");
+        assert_eq!(format(&three, 4, 4, 3, 4), "四兵平六");
+
+        // Two files with tandem soldiers: the file replaces the piece name
+        let two_files = board(&[
+            (4, 0, PieceType::General, Color::Black),
+            (5, 9, PieceType::General, Color::Red),
+            (2, 6, PieceType::Soldier, Color::Black),
+            (2, 5, PieceType::Soldier, Color::Black),
+            (6, 6, PieceType::Soldier, Color::Black),
+            (6, 5, PieceType::Soldier, Color::Black),
+        ]);
+        assert_eq!(format(&two_files, 2, 6, 2, 7), "前3进1");
+        assert_eq!(format(&two_files, 6, 5, 7, 5), "后7平8");
+    }
+
+    #[test]
+    fn test_parse_variants() {
+        let mut board = GameState::new().board;
+        let cannon = Move::from_coords(7, 7, 4, 7).unwrap();
+        for text in [
+            "炮二平五",
+            "砲二平五",
+            "炮2平5",
+            " 炮 二 平 五 ",
+            "炮２平５",
+        ] {
+            assert_eq!(
+                parse_move(&mut board, Color::Red, text),
+                Ok(cannon),
+                "{}",
+                text
+            );
+        }
+
+        let horse = Move::from_coords(7, 0, 6, 2).unwrap();
+        for text in ["马8进7", "馬8進7", "马八进七", "马８进７"] {
+            assert_eq!(
+                parse_move(&mut board, Color::Black, text),
+                Ok(horse),
+                "{}",
+                text
+            );
+        }
+
+        assert_eq!(
+            parse_move(&mut board, Color::Red, "俥一進一"),
+            Ok(Move::from_coords(8, 9, 8, 8).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_parse_tandem() {
+        let mut board = board(&[
+            (4, 9, PieceType::General, Color::Red),
+            (3, 0, PieceType::General, Color::Black),
+            (2, 3, PieceType::Chariot, Color::Red),
+            (2, 6, PieceType::Chariot, Color::Red),
+        ]);
+        assert_eq!(
+            parse_move(&mut board, Color::Red, "後車平四"),
+            Ok(Move::from_coords(2, 6, 5, 6).unwrap())
+        );
+        assert_eq!(
+            parse_move(&mut board, Color::Red, "前车退一"),
+            Ok(Move::from_coords(2, 3, 2, 4).unwrap())
+        );
+        // The plain form is ambiguous once two chariots share the file
+        assert!(parse_move(&mut board, Color::Red, "车七进一").is_err());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let mut board = GameState::new().board;
+        // Blocked horse leg, a chariot running into its own soldier, nonsense
+        for (color, text) in [
+            (Color::Red, "马二进四"),
+            (Color::Black, "车1进3"),
+            (Color::Red, "炮二平五平"),
+            (Color::Red, ""),
+            (Color::Red, "h2e2"),
+        ] {
+            assert!(
+                matches!(
+                    parse_move(&mut board, color, text),
+                    Err(ChessError::InvalidNotation(_))
+                ),
+                "{}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn test_every_legal_move_round_trips() {
+        // Walk a deterministic pseudo-random game; at every ply each legal
+        // move must have its own notation that parses back to it
+        let mut board = GameState::new().board;
+        let mut color = Color::Red;
+        let mut seed: u64 = 0x9e37_79b9;
+
+        for _ in 0..150 {
+            let moves = movegen::legal_moves(&mut board, color);
+            if moves.is_empty() {
+                break;
+            }
+            for &mv in &moves {
+                let text = format_move(&board, mv).unwrap();
+                assert_eq!(parse_move(&mut board, color, &text), Ok(mv), "{}", text);
+            }
+
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            let undo = board.make_move(moves[(seed >> 33) as usize % moves.len()]);
+            if matches!(undo.captured, Some(piece) if piece.piece_type == PieceType::General) {
+                break;
+            }
+            color = color.opponent();
+        }
+    }
+
+    #[test]
+    fn test_manager_move_list() {
+        let mut manager = GameStateManager::new();
+        for (from_x, from_y, to_x, to_y) in [(7, 7, 4, 7), (7, 0, 6, 2), (4, 7, 4, 3)] {
+            manager.make_move(from_x, from_y, to_x, to_y).unwrap();
+        }
+        assert_eq!(manager.chinese_moves(), ["炮二平五", "马8进7", "炮五进四"]);
+
+        manager.undo_move().unwrap();
+        assert_eq!(manager.chinese_moves(), ["炮二平五", "马8进7"]);
+
+        // A game starting with Black to move has no placeholder entry
+        let mut manager = GameStateManager::from_fen(
+            "rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C2C4/9/RNBAKABNR b",
+        )
+        .unwrap();
+        manager.make_move(1, 0, 2, 2).unwrap();
+        manager.make_move(7, 9, 6, 7).unwrap();
+        assert_eq!(manager.chinese_moves(), ["马2进3", "马二进三"]);
+
+        // A record that no longer matches its position is shown in ICCS
+        manager.positions[1].board.set_piece(7, 9, None);
+        assert_eq!(manager.chinese_moves(), ["马2进3", "h0g2"]);
+    }
+}
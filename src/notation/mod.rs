@@ -0,0 +1,204 @@
+//! Move notations that describe a move relative to the position it is played
+//! in: the piece, the file it stands on counted from the mover's right, the
+//! direction and the distance or destination file.
+
+pub mod chinese;
+pub mod wxf;
+
+use crate::board::Board;
+use crate::game::GameStateManager;
+use crate::history::MoveRecord;
+use crate::iccs;
+use crate::moves::Move;
+use crate::piece::{Color, Piece, PieceType};
+use crate::square::Square;
+use crate::ChessError;
+use serde::{Deserialize, Serialize};
+
+/// The text formats a move can be written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MoveFormat {
+    /// Coordinates such as h2e2
+    Iccs,
+    /// 炮二平五
+    Chinese,
+    /// C2=5
+    Wxf,
+}
+
+impl MoveFormat {
+    /// Formats a move from the position before it is played.
+    pub fn format_move(self, board: &Board, mv: Move) -> Result<String, ChessError> {
+        match self {
+            MoveFormat::Iccs => Ok(iccs::format_move(mv)),
+            MoveFormat::Chinese => chinese::format_move(board, mv),
+            MoveFormat::Wxf => wxf::format_move(board, mv),
+        }
+    }
+
+    /// Formats a recorded move from the position before it was played.
+    pub fn format_record(self, board: &Board, record: &MoveRecord) -> Result<String, ChessError> {
+        let mv = record.mv();
+        if board.piece_at(mv.from()) != Some(record.piece) {
+            return Err(ChessError::InvalidMove);
+        }
+        self.format_move(board, mv)
+    }
+
+    /// Parses a move for `color` in the given position. ICCS moves are only
+    /// checked for syntax, not legality.
+    pub fn parse_move(
+        self,
+        board: &mut Board,
+        color: Color,
+        text: &str,
+    ) -> Result<Move, ChessError> {
+        match self {
+            MoveFormat::Iccs => iccs::parse_move(text),
+            MoveFormat::Chinese => chinese::parse_move(board, color, text),
+            MoveFormat::Wxf => wxf::parse_move(board, color, text),
+        }
+    }
+
+    /// Parses a move written in any of the formats. Their spellings never
+    /// overlap, so the first format that accepts the text wins.
+    pub fn parse_any(board: &mut Board, color: Color, text: &str) -> Result<Move, ChessError> {
+        [MoveFormat::Iccs, MoveFormat::Wxf, MoveFormat::Chinese]
+            .into_iter()
+            .find_map(|format| format.parse_move(board, color, text).ok())
+            .ok_or_else(|| {
+                ChessError::InvalidNotation(format!("'{}' is not a legal move", text.trim()))
+            })
+    }
+}
+
+impl GameStateManager {
+    /// The moves played so far in the given format, one entry per move.
+    /// A move that cannot be written in `format` falls back to ICCS rather
+    /// than leaving a gap in the list.
+    pub fn notated_moves(&self, format: MoveFormat) -> Vec<String> {
+        self.history
+            .moves()
+            .zip(&self.positions)
+            .map(|(record, position)| {
+                format
+                    .format_record(&position.board, record)
+                    .unwrap_or_else(|_| iccs::format_move(record.mv()))
+            })
+            .collect()
+    }
+}
+
+/// Direction of a move as seen by the side making it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Direction {
+    Forward,
+    Backward,
+    Sideways,
+}
+
+/// Which piece moves, told apart from others of its kind where needed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Origin {
+    /// The only piece of its kind on the file
+    File(usize),
+    /// One of `count` pieces of its kind on `file`, `index` counting from the
+    /// front. `shared` is set when another file holds several of them too.
+    Tandem {
+        file: usize,
+        index: usize,
+        count: usize,
+        shared: bool,
+    },
+}
+
+/// A move broken down into the parts every file-based notation uses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Description {
+    pub piece: Piece,
+    pub origin: Origin,
+    pub direction: Direction,
+    /// Steps for pieces moving along a file, otherwise the destination file
+    pub amount: usize,
+}
+
+/// Numbers the files from the given side's right, 1 to 9.
+pub(crate) fn file_number(color: Color, x: usize) -> usize {
+    match color {
+        Color::Red => 9 - x,
+        Color::Black => x + 1,
+    }
+}
+
+/// Describes a move from the position before it is played.
+pub(crate) fn describe(board: &Board, mv: Move) -> Result<Description, ChessError> {
+    let piece = board.piece_at(mv.from()).ok_or(ChessError::InvalidMove)?;
+    let color = piece.color;
+    let (_, from_y, to_x, to_y) = mv.to_coords();
+
+    let direction = if from_y == to_y {
+        Direction::Sideways
+    } else if (to_y < from_y) == (color == Color::Red) {
+        Direction::Forward
+    } else {
+        Direction::Backward
+    };
+
+    // Pieces moving along a file give the distance, the others land on a new
+    // file and give that instead
+    let amount = if moves_straight(piece.piece_type) && direction != Direction::Sideways {
+        from_y.abs_diff(to_y)
+    } else {
+        file_number(color, to_x)
+    };
+
+    Ok(Description {
+        piece,
+        origin: origin(board, mv.from(), piece),
+        direction,
+        amount,
+    })
+}
+
+fn origin(board: &Board, from: Square, piece: Piece) -> Origin {
+    let file = file_number(piece.color, from.x());
+
+    // Advisors and elephants on one file can never reach the same square in
+    // the same direction, so the file always identifies them
+    if matches!(piece.piece_type, PieceType::Advisor | PieceType::Elephant) {
+        return Origin::File(file);
+    }
+
+    let mut same_file = ranks_of(board, piece, from.x());
+    if same_file.len() < 2 {
+        return Origin::File(file);
+    }
+
+    // Front is towards the opponent
+    if piece.color == Color::Black {
+        same_file.reverse();
+    }
+    let index = same_file.iter().position(|&y| y == from.y()).unwrap();
+    let shared = (0..9).any(|x| x != from.x() && ranks_of(board, piece, x).len() >= 2);
+
+    Origin::Tandem {
+        file,
+        index,
+        count: same_file.len(),
+        shared,
+    }
+}
+
+// Ranks holding the given piece on file x, from the top of the board down
+fn ranks_of(board: &Board, piece: Piece, x: usize) -> Vec<usize> {
+    (0..10)
+        .filter(|&y| board.get_piece(x, y) == Some(piece))
+        .collect()
+}
+
+fn moves_straight(piece_type: PieceType) -> bool {
+    matches!(
+        piece_type,
+        PieceType::General | PieceType::Chariot | PieceType::Cannon | PieceType::Soldier
+    )
+}
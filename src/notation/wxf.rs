@@ -0,0 +1,261 @@
+use super::{describe, Direction, MoveFormat, Origin};
+use crate::board::Board;
+use crate::game::GameStateManager;
+use crate::movegen;
+use crate::moves::Move;
+use crate::piece::{Color, Piece, PieceType};
+use crate::ChessError;
+
+// WXF writes a piece letter, its file, an operator (+ forward, - backward,
+// = sideways) and the distance or destination file, with both sides counting
+// files from their own right: C2=5, H8+7. Two pieces of a kind on one file are
+// +C and -C for the front and rear one; three or more soldiers are numbered
+// from the front as 1P, 2P, 3P. When two files hold several soldiers the file
+// is kept as well: +P7+1.
+
+/// Formats a move such as C2=5 from the position before it is played.
+pub fn format_move(board: &Board, mv: Move) -> Result<String, ChessError> {
+    let description = describe(board, mv)?;
+    let letter = piece_letter(description.piece);
+
+    let mut text = match description.origin {
+        Origin::File(file) => format!("{}{}", letter, file),
+        Origin::Tandem {
+            file,
+            index,
+            count,
+            shared,
+        } => {
+            let position = match (count, index) {
+                (2, 0) => "+".to_string(),
+                (2, _) => "-".to_string(),
+                _ => (index + 1).to_string(),
+            };
+            if shared {
+                format!("{}{}{}", position, letter, file)
+            } else {
+                format!("{}{}", position, letter)
+            }
+        }
+    };
+
+    text.push(match description.direction {
+        Direction::Forward => '+',
+        Direction::Backward => '-',
+        Direction::Sideways => '=',
+    });
+    text.push_str(&description.amount.to_string());
+    Ok(text)
+}
+
+/// Parses a move such as H8+7 for `color` in the given position. Letters may
+/// be lower case, '.' is accepted for '=' and B and N for E and H.
+pub fn parse_move(board: &mut Board, color: Color, text: &str) -> Result<Move, ChessError> {
+    let wanted = normalize(text);
+    if !wanted.is_empty() {
+        for mv in movegen::legal_moves(board, color) {
+            if format_move(board, mv).as_deref() == Ok(wanted.as_str()) {
+                return Ok(mv);
+            }
+        }
+    }
+    Err(ChessError::InvalidNotation(format!(
+        "'{}' is not a legal move in WXF notation",
+        text.trim()
+    )))
+}
+
+fn piece_letter(piece: Piece) -> char {
+    match piece.piece_type {
+        PieceType::General => 'K',
+        PieceType::Advisor => 'A',
+        PieceType::Elephant => 'E',
+        PieceType::Horse => 'H',
+        PieceType::Chariot => 'R',
+        PieceType::Cannon => 'C',
+        PieceType::Soldier => 'P',
+    }
+}
+
+fn normalize(text: &str) -> String {
+    text.chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| match c.to_ascii_uppercase() {
+            '.' => '=',
+            'B' => 'E',
+            'N' => 'H',
+            c => c,
+        })
+        .collect()
+}
+
+impl GameStateManager {
+    /// The moves played so far in WXF notation, one entry per move.
+    pub fn wxf_moves(&self) -> Vec<String> {
+        self.notated_moves(MoveFormat::Wxf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{GameState, Termination};
+
+    // Central cannon against screen horses, ending with a soldier screening
+    // the cannon for mate
+    const SAMPLE_GAME: [&str; 41] = [
+        "C2=5", "H8+7", "H2+3", "R9=8", "R1=2", "H2+3", "P7+1", "P7+1", "R2+6", "C8=9", "R2=3",
+        "C9-1", "H8+7", "A4+5", "H7+6", "C9=7", "R3=4", "H7+8", "C8=7", "R1=2", "R9=8", "C2+4",
+        "R8+3", "H8+7", "R8-2", "E3+5", "R8=4", "R8+2", "+R+3", "A5-6", "R4+8", "K5+1", "H6+5",
+        "E5+3", "H5+3", "K5=4", "C5=6", "C7=6", "P7+1", "R2+4", "P7=6",
+    ];
+
+    fn board(pieces: &[(usize, usize, PieceType, Color)]) -> Board {
+        let mut board = Board::new();
+        for &(x, y, piece_type, color) in pieces {
+            board.set_piece(x, y, Some(Piece::new(piece_type, color)));
+        }
+        board
+    }
+
+    fn format(board: &Board, from_x: usize, from_y: usize, to_x: usize, to_y: usize) -> String {
+        format_move(
+            board,
+            Move::from_coords(from_x, from_y, to_x, to_y).unwrap(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_opening_moves() {
+        let board = GameState::new().board;
+        assert_eq!(format(&board, 7, 7, 4, 7), "C2=5");
+        assert_eq!(format(&board, 7, 9, 6, 7), "H2+3");
+        assert_eq!(format(&board, 6, 9, 4, 7), "E3+5");
+        assert_eq!(format(&board, 4, 9, 4, 8), "K5+1");
+        // Black uses the same digits, counted from its own right
+        assert_eq!(format(&board, 7, 0, 6, 2), "H8+7");
+        assert_eq!(format(&board, 1, 2, 4, 2), "C2=5");
+        assert_eq!(format(&board, 3, 0, 4, 1), "A4+5");
+    }
+
+    #[test]
+    fn test_tandem_pieces() {
+        let mut board = board(&[
+            (4, 9, PieceType::General, Color::Red),
+            (3, 0, PieceType::General, Color::Black),
+            (2, 3, PieceType::Chariot, Color::Red),
+            (2, 6, PieceType::Chariot, Color::Red),
+            (1, 2, PieceType::Cannon, Color::Black),
+            (1, 5, PieceType::Cannon, Color::Black),
+            (6, 2, PieceType::Soldier, Color::Red),
+            (6, 3, PieceType::Soldier, Color::Red),
+            (6, 4, PieceType::Soldier, Color::Red),
+        ]);
+        assert_eq!(format(&board, 2, 3, 2, 2), "+R+1");
+        assert_eq!(format(&board, 2, 6, 5, 6), "-R=4");
+        assert_eq!(format(&board, 1, 5, 1, 6), "+C+1");
+        assert_eq!(format(&board, 1, 2, 1, 1), "-C-1");
+        assert_eq!(format(&board, 6, 2, 6, 1), "1P+1");
+        assert_eq!(format(&board, 6, 3, 5, 3), "2P=4");
+        assert_eq!(format(&board, 6, 4, 7, 4), "3P=2");
+
+        // A second file with several soldiers brings the file number back
+        board.set_piece(8, 3, Some(Piece::new(PieceType::Soldier, Color::Red)));
+        board.set_piece(8, 4, Some(Piece::new(PieceType::Soldier, Color::Red)));
+        assert_eq!(format(&board, 8, 3, 8, 2), "+P1+1");
+        assert_eq!(format(&board, 6, 3, 5, 3), "2P3=4");
+    }
+
+    #[test]
+    fn test_parse_variants() {
+        let mut board = GameState::new().board;
+        let cannon = Move::from_coords(7, 7, 4, 7).unwrap();
+        for text in ["C2=5", "c2=5", "C2.5", " C2 = 5 "] {
+            assert_eq!(
+                parse_move(&mut board, Color::Red, text),
+                Ok(cannon),
+                "{}",
+                text
+            );
+        }
+        assert_eq!(
+            parse_move(&mut board, Color::Black, "N8+7"),
+            Ok(Move::from_coords(7, 0, 6, 2).unwrap())
+        );
+        assert_eq!(
+            parse_move(&mut board, Color::Red, "B7+5"),
+            Ok(Move::from_coords(2, 9, 4, 7).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let mut board = GameState::new().board;
+        // Blocked horse leg, a chariot running into its own soldier, nonsense
+        for (color, text) in [
+            (Color::Red, "H2+4"),
+            (Color::Black, "R1+3"),
+            (Color::Red, "+C=5"),
+            (Color::Red, ""),
+            (Color::Red, "炮二平五"),
+        ] {
+            assert!(
+                matches!(
+                    parse_move(&mut board, color, text),
+                    Err(ChessError::InvalidNotation(_))
+                ),
+                "{}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn test_sample_game_round_trips() {
+        let mut manager = GameStateManager::new();
+        for text in SAMPLE_GAME {
+            let mv =
+                parse_move(&mut manager.state.board, manager.state.current_turn, text).unwrap();
+            manager.play_move(mv).unwrap();
+        }
+        assert_eq!(manager.wxf_moves(), SAMPLE_GAME);
+        assert_eq!(
+            manager
+                .state
+                .result
+                .as_ref()
+                .map(|result| result.termination),
+            Some(Termination::Checkmate)
+        );
+        assert_eq!(manager.state.winner, Some(Color::Red));
+
+        // The same game in Chinese notation, sharing the file counting
+        let chinese = manager.chinese_moves();
+        assert_eq!(chinese[0], "炮二平五");
+        assert_eq!(chinese[28], "前车进三");
+        assert_eq!(chinese[40], "兵七平六");
+    }
+
+    #[test]
+    fn test_every_legal_move_round_trips() {
+        // Every legal move at each ply of the sample game has its own
+        // notation that parses back to it
+        let mut manager = GameStateManager::new();
+        for text in SAMPLE_GAME {
+            let board = &mut manager.state.board;
+            let color = manager.state.current_turn;
+            for mv in movegen::legal_moves(board, color) {
+                let formatted = format_move(board, mv).unwrap();
+                assert_eq!(
+                    parse_move(board, color, &formatted),
+                    Ok(mv),
+                    "{}",
+                    formatted
+                );
+            }
+            let mv = parse_move(board, color, text).unwrap();
+            manager.play_move(mv).unwrap();
+        }
+    }
+}
@@ -0,0 +1,273 @@
+use crate::board::Board;
+use crate::game::GameState;
+use crate::movegen;
+use crate::moves::Move;
+use crate::piece::Color;
+
+/// Counts the leaf nodes of the legal move tree to the given depth, with
+/// `color` to move. The board is restored before returning.
+pub fn perft(board: &mut Board, color: Color, depth: usize) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+
+    let moves = movegen::legal_moves(board, color);
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+
+    let mut nodes = 0;
+    for mv in moves {
+        let undo = board.make_move(mv);
+        nodes += perft(board, color.opponent(), depth - 1);
+        board.unmake_move(undo);
+    }
+    nodes
+}
+
+/// Breaks the perft count down by root move.
+pub fn divide(board: &mut Board, color: Color, depth: usize) -> Vec<(Move, u64)> {
+    if depth == 0 {
+        return Vec::new();
+    }
+
+    let mut counts = Vec::new();
+    for mv in movegen::legal_moves(board, color) {
+        let undo = board.make_move(mv);
+        counts.push((mv, perft(board, color.opponent(), depth - 1)));
+        board.unmake_move(undo);
+    }
+    counts
+}
+
+impl GameState {
+    /// Counts the leaf nodes of the legal move tree from this position. The
+    /// board is searched in place and restored before returning.
+    pub fn perft(&mut self, depth: usize) -> u64 {
+        perft(&mut self.board, self.current_turn, depth)
+    }
+
+    /// Breaks the perft count from this position down by root move.
+    pub fn divide(&mut self, depth: usize) -> Vec<(Move, u64)> {
+        divide(&mut self.board, self.current_turn, depth)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::GameStateManager;
+    use crate::piece::{Piece, PieceType};
+    use crate::rules;
+    use crate::square::Square;
+
+    fn position(pieces: &[(usize, usize, PieceType, Color)], side_to_move: Color) -> GameState {
+        let mut board = Board::new();
+        for &(x, y, piece_type, color) in pieces {
+            board.set_piece(x, y, Some(Piece::new(piece_type, color)));
+        }
+        GameState::from_position(board, side_to_move).unwrap()
+    }
+
+    // Central cannon against screen horses, after the cannon takes the middle soldier
+    fn opening() -> GameState {
+        let mut manager = GameStateManager::new();
+        for (from_x, from_y, to_x, to_y) in [
+            (7, 7, 4, 7),
+            (7, 0, 6, 2),
+            (7, 9, 6, 7),
+            (8, 0, 7, 0),
+            (8, 9, 7, 9),
+            (6, 3, 6, 4),
+            (7, 9, 7, 3),
+            (1, 0, 2, 2),
+            (4, 7, 4, 3),
+        ] {
+            manager.make_move(from_x, from_y, to_x, to_y).unwrap();
+        }
+        manager.state
+    }
+
+    // Red chariot pinned between the generals, crossed soldiers on both sides
+    fn pinned_chariot() -> GameState {
+        use Color::{Black, Red};
+        use PieceType::*;
+        position(
+            &[
+                (4, 9, General, Red),
+                (4, 8, Advisor, Red),
+                (4, 5, Chariot, Red),
+                (1, 4, Cannon, Red),
+                (6, 3, Horse, Red),
+                (3, 2, Soldier, Red),
+                (5, 3, Soldier, Red),
+                (4, 0, General, Black),
+                (5, 0, Advisor, Black),
+                (4, 2, Elephant, Black),
+                (2, 1, Horse, Black),
+                (4, 4, Cannon, Black),
+                (0, 8, Chariot, Black),
+                (3, 7, Soldier, Black),
+            ],
+            Red,
+        )
+    }
+
+    // Black to move with the generals on neighbouring files
+    fn open_files() -> GameState {
+        use Color::{Black, Red};
+        use PieceType::*;
+        position(
+            &[
+                (3, 9, General, Red),
+                (2, 2, Horse, Red),
+                (5, 1, Soldier, Red),
+                (6, 5, Chariot, Red),
+                (4, 0, General, Black),
+                (4, 1, Advisor, Black),
+                (4, 7, Cannon, Black),
+                (8, 8, Chariot, Black),
+                (3, 6, Soldier, Black),
+            ],
+            Black,
+        )
+    }
+
+    // Red in check from a cannon using its own soldier as the screen
+    fn cannon_check() -> GameState {
+        use Color::{Black, Red};
+        use PieceType::*;
+        position(
+            &[
+                (4, 9, General, Red),
+                (3, 9, Advisor, Red),
+                (5, 9, Advisor, Red),
+                (0, 9, Chariot, Red),
+                (2, 7, Horse, Red),
+                (4, 6, Soldier, Red),
+                (6, 9, Elephant, Red),
+                (5, 0, General, Black),
+                (4, 4, Cannon, Black),
+                (3, 1, Chariot, Black),
+                (6, 5, Horse, Black),
+                (2, 0, Elephant, Black),
+            ],
+            Red,
+        )
+    }
+
+    // Reference perft through the rule validator, trying all 90x90 square pairs
+    fn scan_perft(board: &mut Board, color: Color, depth: usize) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        let mut nodes = 0;
+        for from in Square::all() {
+            for to in Square::all() {
+                let mv = Move::new(from, to);
+                if rules::validate_legal_move(board, mv, color).is_ok() {
+                    let undo = board.make_move(mv);
+                    nodes += scan_perft(board, color.opponent(), depth - 1);
+                    board.unmake_move(undo);
+                }
+            }
+        }
+        nodes
+    }
+
+    #[test]
+    fn test_start_position() {
+        let mut state = GameState::new();
+        assert_eq!(state.perft(1), 44);
+        assert_eq!(state.perft(2), 1920);
+        assert_eq!(state.perft(3), 79666);
+    }
+
+    #[test]
+    #[ignore = "takes several seconds in debug builds; run by CI in release mode"]
+    fn test_start_position_depth_4() {
+        assert_eq!(GameState::new().perft(4), 3290240);
+    }
+
+    // Published counts for the second position of the common xiangqi perft
+    // suite, a middlegame with cannons and horses across the river
+    const MIDDLEGAME: &str =
+        "r1ba1a3/4kn3/2n1b4/pNp1p1p1p/4c4/6P2/P1P2R2P/1CcC5/9/2BAKAB2 w - - 0 1";
+
+    #[test]
+    fn test_middlegame_position() {
+        let mut state = GameState::from_fen(MIDDLEGAME).unwrap();
+        assert_eq!(state.perft(1), 38);
+        assert_eq!(state.perft(2), 1128);
+        assert_eq!(state.perft(3), 43929);
+    }
+
+    #[test]
+    #[ignore = "takes several seconds in debug builds; run by CI in release mode"]
+    fn test_middlegame_position_depth_4() {
+        assert_eq!(GameState::from_fen(MIDDLEGAME).unwrap().perft(4), 1339047);
+    }
+
+    // Published counts for the third to fifth positions of the same suite:
+    // a crowded middlegame and two endings with few pieces left
+    const SUITE: [(&str, [u64; 4]); 3] = [
+        (
+            "1rbaka2R/5r3/6n2/2p1p1p2/4P1bP1/PpC3Bc1/1nPR2P2/2N2AN2/1c2K4/9 w - - 0 1",
+            [50, 2185, 100925, 4314378],
+        ),
+        (
+            "5a3/3k5/3aR4/9/5r3/5n3/9/3A1A3/5K3/2BC2B2 w - - 0 1",
+            [25, 424, 9850, 202884],
+        ),
+        (
+            "CRN1k1b2/3ca4/4ba3/9/2nr5/9/9/4B4/4A4/4KA3 w - - 0 1",
+            [28, 516, 14808, 395483],
+        ),
+    ];
+
+    #[test]
+    fn test_suite_positions() {
+        for (fen, counts) in SUITE {
+            let mut state = GameState::from_fen(fen).unwrap();
+            for depth in 1..=3 {
+                assert_eq!(
+                    state.perft(depth),
+                    counts[depth - 1],
+                    "{fen} at depth {depth}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    #[ignore = "takes several seconds in debug builds; run by CI in release mode"]
+    fn test_suite_positions_depth_4() {
+        for (fen, counts) in SUITE {
+            assert_eq!(
+                GameState::from_fen(fen).unwrap().perft(4),
+                counts[3],
+                "{fen}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_matches_validator_scan() {
+        for state in [opening(), pinned_chariot(), open_files(), cannon_check()] {
+            let mut board = state.board.clone();
+            assert_eq!(
+                perft(&mut board, state.current_turn, 2),
+                scan_perft(&mut board, state.current_turn, 2)
+            );
+            assert_eq!(board, state.board);
+        }
+    }
+
+    #[test]
+    fn test_divide_sums_to_perft() {
+        let mut state = cannon_check();
+        let counts = state.divide(3);
+        assert_eq!(counts.len(), 4);
+        assert_eq!(counts.iter().map(|&(_, nodes)| nodes).sum::<u64>(), 3046);
+    }
+}
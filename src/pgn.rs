@@ -0,0 +1,732 @@
+use crate::annotation::{Annotation, Arrow, Highlight, MarkColor, Nag};
+use crate::fen::START_FEN;
+use crate::game::{GameState, GameStateManager};
+use crate::iccs;
+use crate::notation::MoveFormat;
+use crate::piece::Color;
+use crate::square::Square;
+use crate::ChessError;
+use std::fmt;
+use std::str::FromStr;
+
+// Xiangqi PGN follows chess PGN: tag pairs such as [Red "..."] followed by
+// numbered move text ending in the result. A FEN tag gives a custom start
+// position and a Format tag names the move notation (ICCS, WXF or Chinese).
+// Moves may be followed by NAGs ($1, or a suffix such as "!?") and a
+// {comment}, in which [%csl Ge2] highlights squares and [%cal Rh2e2] draws
+// arrows, named in ICCS.
+
+/// Tags written first in every exported game, in this order.
+const ROSTER: [&str; 7] = ["Event", "Site", "Date", "Round", "Red", "Black", "Result"];
+
+const MAX_LINE: usize = 80;
+
+/// A game record: tag pairs and the moves as written in the move text.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PgnGame {
+    /// Tag pairs in file order
+    pub tags: Vec<(String, String)>,
+    /// Comment before the first move
+    pub comment: Option<String>,
+    /// Moves without move numbers, in any supported notation
+    pub moves: Vec<String>,
+    /// Annotations of the moves at the same index; may be shorter than
+    /// `moves` when the later ones have none
+    pub annotations: Vec<Annotation>,
+}
+
+impl PgnGame {
+    /// Records a played game with its moves in the given format. The
+    /// roster tags are set to "?" except for the result, and the FEN tag is
+    /// added when the game did not start from the standard position.
+    pub fn from_manager(manager: &GameStateManager, format: MoveFormat) -> Self {
+        let mut game = Self::default();
+        for name in ROSTER {
+            game.set_tag(name, "?");
+        }
+        game.set_tag("Result", result_text(&manager.state));
+
+        let fen = manager.start_fen();
+        if fen != START_FEN {
+            game.set_tag("FEN", &fen);
+        }
+        game.set_tag(
+            "Format",
+            match format {
+                MoveFormat::Iccs => "ICCS",
+                MoveFormat::Chinese => "Chinese",
+                MoveFormat::Wxf => "WXF",
+            },
+        );
+
+        game.comment = manager.tree.comment().map(str::to_string);
+        game.moves = manager.notated_moves(format);
+        game.annotations = manager
+            .history
+            .moves()
+            .map(|record| record.annotation.clone())
+            .collect();
+        game
+    }
+
+    /// Replays the game from its start position. The moves are checked for
+    /// legality but the positions are only judged at the end, so a game that
+    /// went on past a repetition or the move limit still loads. A decisive
+    /// result that the moves do not reach on the board is applied as a
+    /// resignation, a drawn one as an agreed draw.
+    pub fn to_manager(&self) -> Result<GameStateManager, ChessError> {
+        let mut manager = match self.tag("FEN") {
+            Some(fen) => GameStateManager::from_fen(fen)?,
+            None => GameStateManager::new(),
+        };
+        manager.tree.set_comment(self.comment.clone());
+
+        for (ply, text) in self.moves.iter().enumerate() {
+            let mv =
+                MoveFormat::parse_any(&mut manager.state.board, manager.state.current_turn, text)
+                    .map_err(|_| invalid(format!("cannot read move {} '{}'", ply + 1, text)))?;
+            manager
+                .replay_move(mv)
+                .map_err(|err| invalid(format!("illegal move {} '{}': {}", ply + 1, text, err)))?;
+            if let Some(annotation) = self.annotations.get(ply).filter(|a| !a.is_empty()) {
+                manager.annotate_move(manager.current_node(), annotation.clone())?;
+            }
+        }
+
+        if !manager.state.is_ended {
+            manager.update_status();
+        }
+        if !manager.state.is_ended {
+            match self.tag("Result") {
+                Some("1-0") => manager.resign(Color::Black)?,
+                Some("0-1") => manager.resign(Color::Red)?,
+                Some("1/2-1/2") => manager.agree_draw()?,
+                _ => {}
+            }
+        }
+        Ok(manager)
+    }
+
+    /// Returns the value of a tag.
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Sets a tag, replacing its value if it is already present.
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(tag, _)| tag == name) {
+            Some((_, old)) => *old = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    /// Returns the annotation of a move, creating an empty one if needed.
+    pub fn annotation_mut(&mut self, ply: usize) -> &mut Annotation {
+        if self.annotations.len() <= ply {
+            self.annotations.resize(ply + 1, Annotation::default());
+        }
+        &mut self.annotations[ply]
+    }
+
+    // Move number and side to move of the first move
+    fn first_move(&self) -> (usize, Color) {
+        self.tag("FEN")
+            .and_then(|fen| GameState::from_fen(fen).ok())
+            .map_or((1, Color::Red), |state| {
+                (state.move_number, state.current_turn)
+            })
+    }
+}
+
+/// Parses the first game of a PGN text. Comments and NAGs are kept with the
+/// move they follow, a comment before the first move with the game;
+/// variations are skipped.
+pub fn parse(text: &str) -> Result<PgnGame, ChessError> {
+    let mut game = PgnGame::default();
+    let mut result = None;
+    let mut chars = text.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '[' => {
+                // A tag after the move text starts the next game
+                if !game.moves.is_empty() || result.is_some() {
+                    break;
+                }
+                chars.next();
+                let (name, value) = parse_tag(&mut chars)?;
+                game.tags.push((name, value));
+            }
+            '{' => {
+                let comment = read_comment(&mut chars)?;
+                match game.moves.len().checked_sub(1) {
+                    Some(ply) => add_comment(game.annotation_mut(ply), &comment),
+                    None => {
+                        let text = comment.split_whitespace().collect::<Vec<_>>().join(" ");
+                        if !text.is_empty() {
+                            game.comment = Some(match game.comment.take() {
+                                Some(before) => format!("{} {}", before, text),
+                                None => text,
+                            });
+                        }
+                    }
+                }
+            }
+            ';' => while chars.next().is_some_and(|c| c != '\n') {},
+            '(' => skip_variation(&mut chars)?,
+            ')' => return Err(invalid("unbalanced ')'".to_string())),
+            _ => {
+                let mut token = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || "[]{}();".contains(c) {
+                        break;
+                    }
+                    token.push(c);
+                    chars.next();
+                }
+                let last = game.moves.len().checked_sub(1);
+                if let (Some(ply), Some(nag)) = (last, Nag::from_symbol(&token)) {
+                    game.annotation_mut(ply).nags.push(nag);
+                    continue;
+                }
+                match token.as_str() {
+                    "1-0" | "0-1" | "1/2-1/2" | "*" => result = Some(token),
+                    _ if token.starts_with('$') => {
+                        if let (Some(ply), Ok(code)) = (last, token[1..].parse()) {
+                            game.annotation_mut(ply).nags.push(Nag(code));
+                        }
+                    }
+                    _ => {
+                        // Move numbers may be attached: "1.h2e2", "12...h9g7".
+                        // Digits without a dot belong to the move, as in 1P+1.
+                        let number = token.len()
+                            - token.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+                        let text = match &token[number..] {
+                            rest if number > 0 && rest.starts_with('.') => {
+                                rest.trim_start_matches('.')
+                            }
+                            _ => token.as_str(),
+                        };
+                        // A suffix such as "!?" is a NAG
+                        let mv = text.trim_end_matches(['!', '?']);
+                        if !mv.is_empty() {
+                            game.moves.push(mv.to_string());
+                            if let Some(nag) = Nag::from_symbol(&text[mv.len()..]) {
+                                game.annotation_mut(game.moves.len() - 1).nags.push(nag);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    if let (Some(result), None) = (result, game.tag("Result")) {
+        game.set_tag("Result", &result);
+    }
+    Ok(game)
+}
+
+impl fmt::Display for PgnGame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, value) in &self.tags {
+            let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+            writeln!(f, "[{} \"{}\"]", name, value)?;
+        }
+        writeln!(f)?;
+
+        let (mut number, mut color) = self.first_move();
+        let mut words = comment_words(self.comment.as_deref().unwrap_or_default());
+        let mut commented = false;
+        for (ply, text) in self.moves.iter().enumerate() {
+            match color {
+                Color::Red => words.push(format!("{}.", number)),
+                // The number is repeated after a comment
+                Color::Black if ply == 0 || commented => words.push(format!("{}...", number)),
+                Color::Black => {}
+            }
+            words.push(text.clone());
+            commented = false;
+            if let Some(annotation) = self.annotations.get(ply) {
+                words.extend(annotation.nags.iter().map(|nag| format!("${}", nag.0)));
+                let comment = comment_words(&comment_text(annotation));
+                commented = !comment.is_empty();
+                words.extend(comment);
+            }
+            if color == Color::Black {
+                number += 1;
+            }
+            color = color.opponent();
+        }
+        words.push(self.tag("Result").unwrap_or("*").to_string());
+
+        let mut line = String::new();
+        for word in words {
+            if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > MAX_LINE {
+                writeln!(f, "{}", line)?;
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&word);
+        }
+        writeln!(f, "{}", line)
+    }
+}
+
+impl FromStr for PgnGame {
+    type Err = ChessError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        parse(text)
+    }
+}
+
+fn result_text(state: &GameState) -> &'static str {
+    match &state.result {
+        Some(result) if result.is_draw() => "1/2-1/2",
+        Some(result) => match result.winner {
+            Some(Color::Red) => "1-0",
+            Some(Color::Black) => "0-1",
+            None => "1/2-1/2",
+        },
+        None => "*",
+    }
+}
+
+type Chars<'a> = std::iter::Peekable<std::str::Chars<'a>>;
+
+// Reads `Name "Value"]` after the opening bracket
+fn parse_tag(chars: &mut Chars) -> Result<(String, String), ChessError> {
+    let mut name = String::new();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() || c == '"' || c == ']' {
+            break;
+        }
+        name.push(c);
+        chars.next();
+    }
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+    if name.is_empty() || chars.next() != Some('"') {
+        return Err(invalid("malformed tag".to_string()));
+    }
+
+    let mut value = String::new();
+    loop {
+        match chars.next() {
+            Some('\\') => match chars.next() {
+                Some(c) => value.push(c),
+                None => break,
+            },
+            Some('"') => {
+                while chars.next_if(|c| c.is_whitespace()).is_some() {}
+                if chars.next() == Some(']') {
+                    return Ok((name, value));
+                }
+                break;
+            }
+            Some(c) => value.push(c),
+            None => break,
+        }
+    }
+    Err(invalid(format!("unterminated tag '{}'", name)))
+}
+
+// Reads a {comment}, the opening brace not yet consumed
+fn read_comment(chars: &mut Chars) -> Result<String, ChessError> {
+    chars.next();
+    let mut comment = String::new();
+    for c in chars.by_ref() {
+        if c == '}' {
+            return Ok(comment);
+        }
+        comment.push(c);
+    }
+    Err(invalid("unterminated comment".to_string()))
+}
+
+// Adds a comment to an annotation, taking out the drawing commands
+fn add_comment(annotation: &mut Annotation, comment: &str) {
+    let mut text = String::new();
+    let mut rest = comment;
+    while let Some(start) = rest.find("[%") {
+        let Some(end) = rest[start..].find(']').map(|end| start + end) else {
+            break;
+        };
+        let command = &rest[start + 2..end];
+        let drawn = match command.split_once(' ') {
+            Some(("cal", marks)) => {
+                annotation
+                    .arrows
+                    .extend(marks.split(',').filter_map(parse_arrow));
+                true
+            }
+            Some(("csl", marks)) => {
+                annotation
+                    .highlights
+                    .extend(marks.split(',').filter_map(parse_highlight));
+                true
+            }
+            _ => false,
+        };
+        let kept = if drawn { start } else { end + 1 };
+        text.push_str(&rest[..kept]);
+        rest = &rest[end + 1..];
+    }
+    text.push_str(rest);
+
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if !text.is_empty() {
+        annotation.comment = Some(match annotation.comment.take() {
+            Some(before) => format!("{} {}", before, text),
+            None => text,
+        });
+    }
+}
+
+// A colored square, "Ge2"
+fn parse_highlight(mark: &str) -> Option<Highlight> {
+    let mark = mark.trim();
+    let color = MarkColor::from_letter(mark.chars().next()?)?;
+    let square = iccs::parse_square(mark.get(1..)?).ok()?;
+    Some(Highlight {
+        x: square.x(),
+        y: square.y(),
+        color,
+    })
+}
+
+// A colored arrow, "Rh2e2"
+fn parse_arrow(mark: &str) -> Option<Arrow> {
+    let mark = mark.trim();
+    let color = MarkColor::from_letter(mark.chars().next()?)?;
+    let from = iccs::parse_square(mark.get(1..3)?).ok()?;
+    let to = iccs::parse_square(mark.get(3..)?).ok()?;
+    Some(Arrow {
+        from_x: from.x(),
+        from_y: from.y(),
+        to_x: to.x(),
+        to_y: to.y(),
+        color,
+    })
+}
+
+// The words of a {comment}, with the braces attached; none when it is empty
+fn comment_words(comment: &str) -> Vec<String> {
+    let mut words: Vec<String> = comment.split_whitespace().map(str::to_string).collect();
+    if let Some(last) = words.len().checked_sub(1) {
+        words[0].insert(0, '{');
+        words[last].push('}');
+    }
+    words
+}
+
+// The comment of an annotation with its drawing commands, without braces
+fn comment_text(annotation: &Annotation) -> String {
+    let square = |x, y| Square::from_xy(x, y).map_or_else(String::new, iccs::format_square);
+    let mut text = annotation
+        .comment
+        .clone()
+        .unwrap_or_default()
+        .replace('}', "");
+    if !annotation.highlights.is_empty() {
+        let marks: Vec<String> = annotation
+            .highlights
+            .iter()
+            .map(|mark| format!("{}{}", mark.color.letter(), square(mark.x, mark.y)))
+            .collect();
+        text.push_str(&format!(" [%csl {}]", marks.join(",")));
+    }
+    if !annotation.arrows.is_empty() {
+        let marks: Vec<String> = annotation
+            .arrows
+            .iter()
+            .map(|mark| {
+                let (from, to) = (
+                    square(mark.from_x, mark.from_y),
+                    square(mark.to_x, mark.to_y),
+                );
+                format!("{}{}{}", mark.color.letter(), from, to)
+            })
+            .collect();
+        text.push_str(&format!(" [%cal {}]", marks.join(",")));
+    }
+    text
+}
+
+fn skip_until(chars: &mut Chars, end: char, error: &str) -> Result<(), ChessError> {
+    chars.next();
+    for c in chars.by_ref() {
+        if c == end {
+            return Ok(());
+        }
+    }
+    Err(invalid(error.to_string()))
+}
+
+// Skips a parenthesized variation, including nested ones and comments
+fn skip_variation(chars: &mut Chars) -> Result<(), ChessError> {
+    let mut depth = 0;
+    while let Some(&c) = chars.peek() {
+        match c {
+            '{' => {
+                skip_until(chars, '}', "unterminated comment")?;
+                continue;
+            }
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    chars.next();
+                    return Ok(());
+                }
+            }
+            _ => {}
+        }
+        chars.next();
+    }
+    Err(invalid("unterminated variation".to_string()))
+}
+
+fn invalid(reason: String) -> ChessError {
+    ChessError::InvalidPgn(reason)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Termination;
+
+    fn play(manager: &mut GameStateManager, moves: &[&str]) {
+        for text in moves {
+            let mv =
+                MoveFormat::parse_any(&mut manager.state.board, manager.state.current_turn, text)
+                    .unwrap();
+            manager.play_move(mv).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_export() {
+        let mut manager = GameStateManager::new();
+        play(&mut manager, &["h2e2", "h9g7", "h0g2", "i9h9"]);
+
+        let mut game = PgnGame::from_manager(&manager, MoveFormat::Iccs);
+        game.set_tag("Red", "Hu Ronghua");
+        assert_eq!(
+            game.to_string(),
+            "[Event \"?\"]\n[Site \"?\"]\n[Date \"?\"]\n[Round \"?\"]\n[Red \"Hu Ronghua\"]\n\
+             [Black \"?\"]\n[Result \"*\"]\n[Format \"ICCS\"]\n\n1. h2e2 h9g7 2. h0g2 i9h9 *\n"
+        );
+
+        let chinese = PgnGame::from_manager(&manager, MoveFormat::Chinese).to_string();
+        assert!(chinese.contains("[Format \"Chinese\"]"));
+        assert!(chinese.ends_with("1. 炮二平五 马8进7 2. 马二进三 车9平8 *\n"));
+    }
+
+    #[test]
+    fn test_round_trip_in_every_format() {
+        let mut manager = GameStateManager::new();
+        play(
+            &mut manager,
+            &[
+                "h2e2", "h9g7", "h0g2", "i9h9", "i0h0", "b9c7", "c3c4", "g6g5", "h0h6", "h7i7",
+                "h6g6", "i7i8", "b0c2", "d9e8", "c2d4", "i8g8", "g6f6", "g7h5", "b2c2", "a9b9",
+            ],
+        );
+
+        for format in [MoveFormat::Iccs, MoveFormat::Chinese, MoveFormat::Wxf] {
+            let text = PgnGame::from_manager(&manager, format).to_string();
+            assert!(text.lines().all(|line| line.chars().count() <= MAX_LINE));
+
+            let game: PgnGame = text.parse().unwrap();
+            assert_eq!(game.moves, manager.notated_moves(format));
+            let reloaded = game.to_manager().unwrap();
+            assert_eq!(reloaded.history, manager.history);
+            assert_eq!(reloaded.state, manager.state);
+        }
+    }
+
+    #[test]
+    fn test_custom_start_and_result() {
+        let fen = "4k4/9/9/9/9/9/9/9/4A4/3K1C3 b - - 12 40";
+        let mut manager = GameStateManager::from_fen(fen).unwrap();
+        play(&mut manager, &["e9e8", "f0f8"]);
+        manager.resign(Color::Black).unwrap();
+
+        let text = PgnGame::from_manager(&manager, MoveFormat::Wxf).to_string();
+        assert!(text.contains(&format!("[FEN \"{}\"]", fen)));
+        assert!(text.contains("[Result \"1-0\"]"));
+        assert!(text.ends_with("40... K5+1 41. C4+8 1-0\n"));
+
+        let reloaded = parse(&text).unwrap().to_manager().unwrap();
+        assert_eq!(reloaded.start_fen(), fen);
+        assert_eq!(reloaded.state.winner, Some(Color::Red));
+        assert_eq!(
+            reloaded.state.result.map(|result| result.termination),
+            Some(Termination::Resignation)
+        );
+    }
+
+    #[test]
+    fn test_game_going_on_after_a_repetition() {
+        // The start position comes up a third time after 4... g7h9, which
+        // would draw a game played here, but the players went on
+        let moves = "1. h0g2 h9g7 2. g2h0 g7h9 3. h0g2 h9g7 4. g2h0 g7h9";
+        let manager = parse(&format!("{} 5. h2e2 *", moves))
+            .unwrap()
+            .to_manager()
+            .unwrap();
+        assert_eq!(manager.history.moves().count(), 9);
+        assert_eq!(manager.state.result, None);
+        assert_eq!(manager.state.current_turn, Color::Black);
+
+        // A game that stops there is judged once the moves are replayed
+        let manager = parse(&format!("{} 1/2-1/2", moves))
+            .unwrap()
+            .to_manager()
+            .unwrap();
+        assert_eq!(
+            manager.state.result.map(|result| result.termination),
+            Some(Termination::Repetition)
+        );
+    }
+
+    #[test]
+    fn test_parse_tolerates_annotations() {
+        let text = r#"
+            [Event "Club \"Open\""]
+            [Red "A"]
+            ; a line comment
+            1.h2e2 {central cannon} h9g7 $1
+            2. H2+3 (2. h0g2 R9=8 (2... i9h9)) 车9平8
+            0-1
+
+            [Event "Next game"]
+            1. b2e2 *
+        "#;
+        let game = parse(text).unwrap();
+        assert_eq!(game.tag("Event"), Some("Club \"Open\""));
+        assert_eq!(game.tag("Result"), Some("0-1"));
+        assert_eq!(game.moves, ["h2e2", "h9g7", "H2+3", "车9平8"]);
+        assert_eq!(
+            game.annotations[0].comment.as_deref(),
+            Some("central cannon")
+        );
+        assert_eq!(game.annotations[1].nags, [Nag::GOOD]);
+
+        let manager = game.to_manager().unwrap();
+        assert_eq!(manager.history.moves().count(), 4);
+        assert_eq!(manager.state.winner, Some(Color::Black));
+
+        // Tag values are escaped on the way out
+        assert!(game
+            .to_string()
+            .starts_with("[Event \"Club \\\"Open\\\"\"]\n"));
+    }
+
+    #[test]
+    fn test_annotations_round_trip() {
+        let text = "1. h2e2!? {Central cannon [%csl Ge2][%cal Rh2e2,Bh0g2]} h9g7 \
+                    2. h0g2?? $14 {Too slow} i9h9 ± *";
+        let game = parse(text).unwrap();
+        assert_eq!(game.moves, ["h2e2", "h9g7", "h0g2", "i9h9"]);
+        let first = &game.annotations[0];
+        assert_eq!(first.comment.as_deref(), Some("Central cannon"));
+        assert_eq!(first.nags, [Nag::INTERESTING]);
+        assert_eq!(
+            first.highlights,
+            [Highlight {
+                x: 4,
+                y: 7,
+                color: MarkColor::Green
+            }]
+        );
+        assert_eq!(first.arrows.len(), 2);
+        assert_eq!(
+            first.arrows[1],
+            Arrow {
+                from_x: 7,
+                from_y: 9,
+                to_x: 6,
+                to_y: 7,
+                color: MarkColor::Blue
+            }
+        );
+        assert_eq!(game.annotations[2].nags, [Nag::BLUNDER, Nag(14)]);
+        assert_eq!(game.annotations[3].nags, [Nag(16)]);
+
+        // The annotations reach the history and come back out unchanged
+        let manager = game.to_manager().unwrap();
+        let records: Vec<_> = manager.history.moves().collect();
+        assert_eq!(&records[0].annotation, first);
+        let exported = PgnGame::from_manager(&manager, MoveFormat::Iccs);
+        assert_eq!(exported.annotations, game.annotations);
+        assert!(exported.to_string().ends_with(
+            "1. h2e2 $5 {Central cannon [%csl Ge2] [%cal Rh2e2,Bh0g2]} 1... h9g7 2. h0g2 $4\n\
+             $14 {Too slow} 2... i9h9 $16 *\n"
+        ));
+        let reparsed = parse(&exported.to_string()).unwrap();
+        assert_eq!(reparsed.annotations, game.annotations);
+    }
+
+    #[test]
+    fn test_game_comment_round_trip() {
+        let game = parse("{Opening\n  notes} 1. h2e2 {Central cannon} h9g7 *").unwrap();
+        assert_eq!(game.comment.as_deref(), Some("Opening notes"));
+        assert_eq!(
+            game.annotations[0].comment.as_deref(),
+            Some("Central cannon")
+        );
+
+        // The comment before the first move is kept on the game tree
+        let manager = game.to_manager().unwrap();
+        assert_eq!(manager.tree.comment(), Some("Opening notes"));
+        let exported = PgnGame::from_manager(&manager, MoveFormat::Iccs);
+        assert_eq!(exported.comment, game.comment);
+        assert!(exported
+            .to_string()
+            .ends_with("\n{Opening notes} 1. h2e2 {Central cannon} 1... h9g7 *\n"));
+    }
+
+    #[test]
+    fn test_errors() {
+        let cases = [
+            ("[Event \"x", "unterminated tag 'Event'"),
+            ("[Event x]", "malformed tag"),
+            ("1. h2e2 {never closed", "unterminated comment"),
+            ("1. h2e2 (h0g2", "unterminated variation"),
+            ("1. h2e2 )", "unbalanced ')'"),
+        ];
+        for (text, reason) in cases {
+            assert_eq!(
+                parse(text),
+                Err(ChessError::InvalidPgn(reason.to_string())),
+                "{}",
+                text
+            );
+        }
+
+        let game = parse("1. h2e2 h9g7 2. h2h9").unwrap();
+        assert_eq!(
+            game.to_manager().err(),
+            Some(ChessError::InvalidPgn(
+                "illegal move 3 'h2h9': Invalid move".to_string()
+            ))
+        );
+        let game = parse("1. h2e2 马二进四").unwrap();
+        assert_eq!(
+            game.to_manager().err(),
+            Some(ChessError::InvalidPgn(
+                "cannot read move 2 '马二进四'".to_string()
+            ))
+        );
+    }
+}
@@ -0,0 +1,470 @@
+use crate::board::Board;
+use crate::game::{GameResult, Termination};
+use crate::movegen;
+use crate::moves::Move;
+use crate::piece::{Color, PieceType};
+use crate::rules;
+use crate::square::Square;
+
+use serde::{Deserialize, Serialize};
+
+/// Number of times a position must occur before a repetition is adjudicated.
+pub const REPETITION_LIMIT: usize = 3;
+
+/// Rule set used to adjudicate repeated positions.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum RuleSet {
+    /// Asian Xiangqi Federation rules: perpetual check and perpetual chase are forbidden
+    #[default]
+    Asian,
+    /// Chinese Xiangqi Association rules: additionally forbids perpetual mate threats (长杀)
+    Chinese,
+}
+
+/// Classification of a single move inside a repetition cycle.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MoveKind {
+    /// The move gives check (将)
+    Check,
+    /// The move newly threatens to win an unprotected piece, or a chariot with a horse or cannon (捉)
+    Chase,
+    /// The move threatens mate in one; only forbidden under Chinese rules (杀)
+    MateThreat,
+    /// The move offers a trade of like pieces (兑)
+    Exchange,
+    /// Any other move (闲)
+    Idle,
+}
+
+impl MoveKind {
+    fn is_forcing(self, rule_set: RuleSet) -> bool {
+        match self {
+            MoveKind::Check | MoveKind::Chase => true,
+            MoveKind::MateThreat => rule_set == RuleSet::Chinese,
+            MoveKind::Exchange | MoveKind::Idle => false,
+        }
+    }
+}
+
+/// A position reached during a game, together with the number of plies played
+/// since the last capture.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Position {
+    pub board: Board,
+    pub side_to_move: Color,
+    pub no_capture_plies: usize,
+}
+
+impl Position {
+    pub fn new(board: Board, side_to_move: Color, no_capture_plies: usize) -> Self {
+        Self {
+            board,
+            side_to_move,
+            no_capture_plies,
+        }
+    }
+
+    /// Returns true when both positions have the same placement and side to move.
+    pub fn is_repeat_of(&self, other: &Position) -> bool {
+        // Compare hashes first so most non-repeats are rejected cheaply
+        self.board.hash() == other.board.hash()
+            && self.side_to_move == other.side_to_move
+            && self.board == other.board
+    }
+}
+
+/// Counts how many times the last position occurs in the list.
+pub fn repetition_count(positions: &[Position]) -> usize {
+    let positions = since_last_capture(positions);
+    match positions.last() {
+        Some(last) => positions.iter().filter(|p| p.is_repeat_of(last)).count(),
+        None => 0,
+    }
+}
+
+/// The positions reached since the last capture. A capture cannot be undone,
+/// so no earlier position can repeat the last one.
+fn since_last_capture(positions: &[Position]) -> &[Position] {
+    match positions.last() {
+        Some(last) => &positions[positions.len().saturating_sub(last.no_capture_plies + 1)..],
+        None => positions,
+    }
+}
+
+/// Classifies the move that turned `before` into `after`, played by `mover`.
+/// Both boards are searched in place and left unchanged.
+pub fn classify_move(
+    before: &mut Board,
+    after: &mut Board,
+    mover: Color,
+    rule_set: RuleSet,
+) -> MoveKind {
+    if rules::is_in_check(after, mover.opponent()) {
+        return MoveKind::Check;
+    }
+
+    let threats_before = threats(before, mover);
+    let new_threats: Vec<Threat> = threats(after, mover)
+        .into_iter()
+        .filter(|t| !threats_before.iter().any(|b| b.target == t.target))
+        .collect();
+
+    if new_threats.iter().any(|t| !t.is_exchange) {
+        return MoveKind::Chase;
+    }
+
+    if rule_set == RuleSet::Chinese && threatens_mate(after, mover) {
+        return MoveKind::MateThreat;
+    }
+
+    if new_threats.is_empty() {
+        MoveKind::Idle
+    } else {
+        MoveKind::Exchange
+    }
+}
+
+/// Adjudicates the last position once it has occurred `REPETITION_LIMIT` times.
+///
+/// Each side's moves since the first of those occurrences are classified. A side
+/// whose moves are all forcing (checks, chases, or under Chinese rules mate
+/// threats, in any mix) commits a forbidden perpetual. If exactly one side does,
+/// it loses; otherwise the game is drawn by repetition.
+pub fn adjudicate(positions: &mut [Position], rule_set: RuleSet) -> Option<GameResult> {
+    if repetition_count(positions) < REPETITION_LIMIT {
+        return None;
+    }
+
+    let start = positions.len() - since_last_capture(positions).len();
+    let positions = &mut positions[start..];
+    let last = positions.last()?;
+    let first = positions.iter().position(|p| p.is_repeat_of(last))?;
+
+    let mut red = Vec::new();
+    let mut black = Vec::new();
+    for index in first..positions.len() - 1 {
+        let (played, rest) = positions.split_at_mut(index + 1);
+        let (before, after) = (&mut played[index], &mut rest[0]);
+        let mover = before.side_to_move;
+        let kind = classify_move(&mut before.board, &mut after.board, mover, rule_set);
+        match mover {
+            Color::Red => red.push(kind),
+            Color::Black => black.push(kind),
+        }
+    }
+
+    let red_violation = violation(&red, rule_set);
+    let black_violation = violation(&black, rule_set);
+
+    match (red_violation, black_violation) {
+        (Some(termination), None) => Some(GameResult::win(Color::Black, termination)),
+        (None, Some(termination)) => Some(GameResult::win(Color::Red, termination)),
+        (Some(_), Some(_)) | (None, None) => Some(GameResult::draw(Termination::Repetition)),
+    }
+}
+
+fn violation(kinds: &[MoveKind], rule_set: RuleSet) -> Option<Termination> {
+    if kinds.is_empty() || !kinds.iter().all(|k| k.is_forcing(rule_set)) {
+        None
+    } else if kinds.iter().all(|k| *k == MoveKind::Check) {
+        Some(Termination::PerpetualCheck)
+    } else {
+        Some(Termination::PerpetualChase)
+    }
+}
+
+struct Threat {
+    target: Square,
+    is_exchange: bool,
+}
+
+/// Lists the opponent pieces `mover` could win: unprotected pieces, plus chariots
+/// attacked by a horse or cannon. Generals and soldiers may chase freely, so
+/// their attacks are ignored, as is the general as a target.
+fn threats(board: &mut Board, mover: Color) -> Vec<Threat> {
+    let mut result: Vec<Threat> = Vec::new();
+
+    for from in pieces_of(board, mover) {
+        let attacker = board.piece_at(from).unwrap();
+        if matches!(attacker.piece_type, PieceType::General | PieceType::Soldier) {
+            continue;
+        }
+
+        for to in pieces_of(board, mover.opponent()) {
+            let target = board.piece_at(to).unwrap();
+            if target.piece_type == PieceType::General
+                || result.iter().any(|t| t.target == to)
+                || rules::validate_legal_move(board, Move::new(from, to), mover).is_err()
+            {
+                continue;
+            }
+
+            let outranks = target.piece_type == PieceType::Chariot
+                && matches!(attacker.piece_type, PieceType::Horse | PieceType::Cannon);
+            if !outranks && is_protected(board, Move::new(from, to)) {
+                continue;
+            }
+
+            // A like piece that can capture back offers a trade rather than a chase
+            let is_exchange = target.piece_type == attacker.piece_type
+                && rules::validate_legal_move(board, Move::new(to, from), mover.opponent()).is_ok();
+
+            result.push(Threat {
+                target: to,
+                is_exchange,
+            });
+        }
+    }
+
+    result
+}
+
+/// Returns true when the opponent could recapture after the given capture,
+/// which is made and taken back on `board`.
+fn is_protected(board: &mut Board, capture: Move) -> bool {
+    let defender = board.piece_at(capture.to()).unwrap().color;
+    let undo = board.make_move(capture);
+    let protected = pieces_of(board, defender).into_iter().any(|from| {
+        rules::validate_legal_move(board, Move::new(from, capture.to()), defender).is_ok()
+    });
+    board.unmake_move(undo);
+    protected
+}
+
+/// Returns true when `mover`, given another move, could checkmate the opponent.
+fn threatens_mate(board: &mut Board, mover: Color) -> bool {
+    for mv in movegen::legal_moves(board, mover) {
+        let undo = board.make_move(mv);
+        let mates = rules::is_in_check(board, mover.opponent())
+            && !movegen::has_legal_move(board, mover.opponent());
+        board.unmake_move(undo);
+        if mates {
+            return true;
+        }
+    }
+    false
+}
+
+fn pieces_of(board: &Board, color: Color) -> Vec<Square> {
+    Square::all()
+        .filter(|&square| matches!(board.piece_at(square), Some(piece) if piece.color == color))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::piece::Piece;
+
+    fn board_with(pieces: &[(usize, usize, PieceType, Color)]) -> Board {
+        let mut board = Board::new();
+        for &(x, y, piece_type, color) in pieces {
+            board.set_piece(x, y, Some(Piece::new(piece_type, color)));
+        }
+        board
+    }
+
+    fn play(positions: &mut Vec<Position>, from: (usize, usize), to: (usize, usize)) {
+        let last = positions.last().unwrap();
+        let mut board = last.board.clone();
+        board.move_piece(from.0, from.1, to.0, to.1);
+        positions.push(Position::new(
+            board,
+            last.side_to_move.opponent(),
+            last.no_capture_plies + 1,
+        ));
+    }
+
+    #[test]
+    fn test_classify_check_chase_and_idle() {
+        let mut before = board_with(&[
+            (4, 9, PieceType::General, Color::Red),
+            (3, 0, PieceType::General, Color::Black),
+            (0, 5, PieceType::Chariot, Color::Red),
+            (8, 3, PieceType::Horse, Color::Black),
+        ]);
+
+        // Chariot to the black general's file gives check
+        let mut after = before.clone();
+        after.move_piece(0, 5, 3, 5);
+        assert_eq!(
+            classify_move(&mut before, &mut after, Color::Red, RuleSet::Asian),
+            MoveKind::Check
+        );
+
+        // Chariot to the horse's rank attacks the unprotected horse
+        let mut after = before.clone();
+        after.move_piece(0, 5, 0, 3);
+        assert_eq!(
+            classify_move(&mut before, &mut after, Color::Red, RuleSet::Asian),
+            MoveKind::Chase
+        );
+
+        // Chariot along its rank threatens nothing new
+        let mut after = before.clone();
+        after.move_piece(0, 5, 1, 5);
+        assert_eq!(
+            classify_move(&mut before, &mut after, Color::Red, RuleSet::Asian),
+            MoveKind::Idle
+        );
+    }
+
+    #[test]
+    fn test_classify_exchange() {
+        let mut before = board_with(&[
+            (4, 9, PieceType::General, Color::Red),
+            (3, 0, PieceType::General, Color::Black),
+            (0, 5, PieceType::Chariot, Color::Red),
+            (8, 3, PieceType::Chariot, Color::Black),
+        ]);
+
+        // Chariot facing the unprotected black chariot offers a trade
+        let mut after = before.clone();
+        after.move_piece(0, 5, 0, 3);
+        assert_eq!(
+            classify_move(&mut before, &mut after, Color::Red, RuleSet::Asian),
+            MoveKind::Exchange
+        );
+    }
+
+    #[test]
+    fn test_mate_threat_only_counts_under_chinese_rules() {
+        let mut before = board_with(&[
+            (3, 9, PieceType::General, Color::Red),
+            (4, 0, PieceType::General, Color::Black),
+            (0, 5, PieceType::Chariot, Color::Red),
+            (8, 4, PieceType::Chariot, Color::Red),
+        ]);
+
+        // Sealing rank 1 threatens mate along the back rank
+        let mut after = before.clone();
+        after.move_piece(8, 4, 8, 1);
+        assert_eq!(
+            classify_move(&mut before, &mut after, Color::Red, RuleSet::Chinese),
+            MoveKind::MateThreat
+        );
+        assert_eq!(
+            classify_move(&mut before, &mut after, Color::Red, RuleSet::Asian),
+            MoveKind::Idle
+        );
+    }
+
+    #[test]
+    fn test_perpetual_check_loses() {
+        // Red chariot checks from two files while the black general shuffles
+        let mut positions = vec![Position::new(
+            board_with(&[
+                (3, 9, PieceType::General, Color::Red),
+                (4, 0, PieceType::General, Color::Black),
+                (0, 5, PieceType::Chariot, Color::Red),
+            ]),
+            Color::Red,
+            0,
+        )];
+
+        play(&mut positions, (0, 5), (4, 5)); // check
+        for _ in 0..2 {
+            assert_eq!(adjudicate(&mut positions, RuleSet::Asian), None);
+            play(&mut positions, (4, 0), (5, 0));
+            play(&mut positions, (4, 5), (5, 5)); // check
+            play(&mut positions, (5, 0), (4, 0));
+            play(&mut positions, (5, 5), (4, 5)); // check
+        }
+
+        assert_eq!(repetition_count(&positions), REPETITION_LIMIT);
+        assert_eq!(
+            adjudicate(&mut positions, RuleSet::Asian),
+            Some(GameResult::win(Color::Black, Termination::PerpetualCheck))
+        );
+        assert_eq!(
+            adjudicate(&mut positions, RuleSet::Chinese),
+            Some(GameResult::win(Color::Black, Termination::PerpetualCheck))
+        );
+    }
+
+    #[test]
+    fn test_perpetual_chase_loses() {
+        // Red chariot keeps chasing the unprotected black horse between two squares
+        let mut positions = vec![Position::new(
+            board_with(&[
+                (3, 9, PieceType::General, Color::Red),
+                (5, 0, PieceType::General, Color::Black),
+                (2, 6, PieceType::Chariot, Color::Red),
+                (1, 2, PieceType::Horse, Color::Black),
+            ]),
+            Color::Red,
+            0,
+        )];
+
+        for _ in 0..2 {
+            assert_eq!(adjudicate(&mut positions, RuleSet::Asian), None);
+            play(&mut positions, (2, 6), (1, 6)); // chase the horse on file 1
+            play(&mut positions, (1, 2), (2, 0));
+            play(&mut positions, (1, 6), (2, 6)); // chase the horse on file 2
+            play(&mut positions, (2, 0), (1, 2));
+        }
+
+        assert_eq!(repetition_count(&positions), REPETITION_LIMIT);
+        assert_eq!(
+            adjudicate(&mut positions, RuleSet::Asian),
+            Some(GameResult::win(Color::Black, Termination::PerpetualChase))
+        );
+        assert_eq!(
+            adjudicate(&mut positions, RuleSet::Chinese),
+            Some(GameResult::win(Color::Black, Termination::PerpetualChase))
+        );
+    }
+
+    #[test]
+    fn test_idle_repetition_is_a_draw() {
+        let mut positions = vec![Position::new(
+            board_with(&[
+                (3, 9, PieceType::General, Color::Red),
+                (5, 0, PieceType::General, Color::Black),
+                (0, 5, PieceType::Chariot, Color::Red),
+            ]),
+            Color::Red,
+            0,
+        )];
+
+        for _ in 0..2 {
+            play(&mut positions, (0, 5), (1, 5));
+            play(&mut positions, (5, 0), (5, 1));
+            play(&mut positions, (1, 5), (0, 5));
+            play(&mut positions, (5, 1), (5, 0));
+        }
+
+        assert_eq!(repetition_count(&positions), REPETITION_LIMIT);
+        assert_eq!(
+            adjudicate(&mut positions, RuleSet::Asian),
+            Some(GameResult::draw(Termination::Repetition))
+        );
+        assert_eq!(
+            adjudicate(&mut positions, RuleSet::Chinese),
+            Some(GameResult::draw(Termination::Repetition))
+        );
+    }
+
+    #[test]
+    fn test_scan_stops_at_the_last_capture() {
+        let board = board_with(&[
+            (3, 9, PieceType::General, Color::Red),
+            (5, 0, PieceType::General, Color::Black),
+        ]);
+        let mut positions = vec![Position::new(board.clone(), Color::Red, 7)];
+        for _ in 0..2 {
+            play(&mut positions, (3, 9), (3, 8));
+            play(&mut positions, (5, 0), (5, 1));
+            play(&mut positions, (3, 8), (3, 9));
+            play(&mut positions, (5, 1), (5, 0));
+        }
+        assert_eq!(repetition_count(&positions), REPETITION_LIMIT);
+
+        // Only the positions since the counter was last reset can repeat
+        positions.push(Position::new(board.clone(), Color::Red, 4));
+        assert_eq!(repetition_count(&positions), 2);
+        positions.push(Position::new(board, Color::Red, 0));
+        assert_eq!(repetition_count(&positions), 1);
+        assert_eq!(adjudicate(&mut positions, RuleSet::Asian), None);
+    }
+}
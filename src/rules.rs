@@ -1,26 +1,68 @@
+use crate::attack;
 use crate::board::Board;
-use crate::piece::{Color, PieceType};
-
+use crate::moves::Move;
+use crate::piece::{Color, Piece, PieceType};
+use crate::square::Square;
+
+// Legal squares as (file, rank) pairs, with rank counted from the owner's back rank
+const PALACE_SQUARES: [(usize, usize); 9] = [
+    (3, 0),
+    (4, 0),
+    (5, 0),
+    (3, 1),
+    (4, 1),
+    (5, 1),
+    (3, 2),
+    (4, 2),
+    (5, 2),
+];
+const ADVISOR_SQUARES: [(usize, usize); 5] = [(3, 0), (5, 0), (4, 1), (3, 2), (5, 2)];
+const ELEPHANT_SQUARES: [(usize, usize); 7] =
+    [(2, 0), (6, 0), (0, 2), (4, 2), (8, 2), (2, 4), (6, 4)];
+
+// Maximum number of pieces of each type per side
+const MAX_PIECES: [(PieceType, usize); 7] = [
+    (PieceType::General, 1),
+    (PieceType::Advisor, 2),
+    (PieceType::Elephant, 2),
+    (PieceType::Horse, 2),
+    (PieceType::Chariot, 2),
+    (PieceType::Cannon, 2),
+    (PieceType::Soldier, 5),
+];
+
+/// Validates a move by the piece movement rules and the flying-general rule.
+/// The move is made and taken back on `board`, which is left unchanged.
 pub fn validate_move(
-    board: &Board,
-    from_x: usize,
-    from_y: usize,
-    to_x: usize,
-    to_y: usize,
+    board: &mut Board,
+    mv: Move,
     current_color: Color,
 ) -> Result<(), crate::ChessError> {
-    // Check if from position is within bounds
-    if from_x >= 9 || from_y >= 10 {
-        return Err(crate::ChessError::OutOfBoard);
-    }
+    validate_on(board, mv, current_color, false)
+}
 
-    // Check if to position is within bounds
-    if to_x >= 9 || to_y >= 10 {
-        return Err(crate::ChessError::OutOfBoard);
-    }
+/// Validates a move and additionally rejects it when it leaves the mover's
+/// own general in check. `board` is left unchanged.
+pub fn validate_legal_move(
+    board: &mut Board,
+    mv: Move,
+    current_color: Color,
+) -> Result<(), crate::ChessError> {
+    validate_on(board, mv, current_color, true)
+}
+
+// Checks the move, making and taking it back on the board to look at the
+// resulting position
+fn validate_on(
+    board: &mut Board,
+    mv: Move,
+    current_color: Color,
+    require_legal: bool,
+) -> Result<(), crate::ChessError> {
+    let (from_x, from_y, to_x, to_y) = mv.to_coords();
 
     // Check if there is a piece at from position
-    let from_piece = match board.get_piece(from_x, from_y) {
+    let from_piece = match board.piece_at(mv.from()) {
         Some(p) => p,
         None => return Err(crate::ChessError::InvalidMove),
     };
@@ -31,7 +73,7 @@ pub fn validate_move(
     }
 
     // Check if to position has own piece
-    if let Some(to_piece) = board.get_piece(to_x, to_y) {
+    if let Some(to_piece) = board.piece_at(mv.to()) {
         if to_piece.color == current_color {
             return Err(crate::ChessError::CannotCaptureOwnPiece);
         }
@@ -39,8 +81,9 @@ pub fn validate_move(
 
     // Validate specific piece movement rules
     match from_piece.piece_type {
-        PieceType::General => validate_general_move(from_x, from_y, to_x, to_y),
-        PieceType::Advisor => validate_advisor_move(from_x, from_y, to_x, to_y),
+        PieceType::General => validate_general_move(from_x, from_y, to_x, to_y, from_piece.color)
+            .or_else(|_| validate_flying_general(board, from_x, from_y, to_x, to_y)),
+        PieceType::Advisor => validate_advisor_move(from_x, from_y, to_x, to_y, from_piece.color),
         PieceType::Elephant => {
             validate_elephant_move(board, from_x, from_y, to_x, to_y, from_piece.color)
         }
@@ -48,15 +91,142 @@ pub fn validate_move(
         PieceType::Chariot => validate_chariot_move(board, from_x, from_y, to_x, to_y),
         PieceType::Cannon => validate_cannon_move(board, from_x, from_y, to_x, to_y),
         PieceType::Soldier => validate_soldier_move(from_x, from_y, to_x, to_y, from_piece.color),
+    }?;
+
+    // The move must not leave the two generals facing each other, nor, when
+    // required, the mover's own general in check
+    let undo = board.make_move(mv);
+    let result = if generals_facing(board) {
+        Err(crate::ChessError::GeneralsFacing)
+    } else if require_legal && is_in_check(board, current_color) {
+        Err(crate::ChessError::InCheck)
+    } else {
+        Ok(())
+    };
+    board.unmake_move(undo);
+
+    result
+}
+
+/// Returns true when any opponent piece can capture the general of the given color.
+pub fn is_in_check(board: &Board, color: Color) -> bool {
+    match find_general(board, color) {
+        Some(general) => attack::is_square_attacked(board, general, color.opponent()),
+        None => false,
+    }
+}
+
+/// Returns true when the piece may ever stand on the given square: generals and
+/// advisors inside their own palace, elephants on their seven points and soldiers
+/// on or beyond their starting points.
+pub fn is_legal_square(piece: Piece, x: usize, y: usize) -> bool {
+    if x >= 9 || y >= 10 {
+        return false;
+    }
+
+    // Rank counted from the owner's back rank
+    let rank = match piece.color {
+        Color::Red => 9 - y,
+        Color::Black => y,
+    };
+
+    match piece.piece_type {
+        PieceType::General => PALACE_SQUARES.contains(&(x, rank)),
+        PieceType::Advisor => ADVISOR_SQUARES.contains(&(x, rank)),
+        PieceType::Elephant => ELEPHANT_SQUARES.contains(&(x, rank)),
+        PieceType::Soldier => rank >= 5 || (rank >= 3 && x.is_multiple_of(2)),
+        PieceType::Horse | PieceType::Chariot | PieceType::Cannon => true,
+    }
+}
+
+/// Checks that a position could arise in a real game: every piece stands on a
+/// legal square, piece counts are within limits, each side has exactly one
+/// general, the generals do not face each other and the side that just moved
+/// is not left in check.
+pub fn validate_position(board: &Board, side_to_move: Color) -> Result<(), crate::ChessError> {
+    let invalid = |reason: String| Err(crate::ChessError::InvalidPosition(reason));
+
+    let mut counts = [[0usize; 7]; 2];
+    for y in 0..10 {
+        for x in 0..9 {
+            if let Some(piece) = board.get_piece(x, y) {
+                if !is_legal_square(piece, x, y) {
+                    return invalid(format!(
+                        "{:?} {:?} cannot stand on ({}, {})",
+                        piece.color, piece.piece_type, x, y
+                    ));
+                }
+                counts[piece.color as usize][piece.piece_type as usize] += 1;
+            }
+        }
+    }
+
+    for color in [Color::Red, Color::Black] {
+        for (piece_type, max) in MAX_PIECES {
+            let count = counts[color as usize][piece_type as usize];
+            if count > max {
+                return invalid(format!(
+                    "{:?} has {} {:?} pieces, at most {} allowed",
+                    color, count, piece_type, max
+                ));
+            }
+        }
+        if counts[color as usize][PieceType::General as usize] == 0 {
+            return invalid(format!("{:?} has no general", color));
+        }
+    }
+
+    if generals_facing(board) {
+        return invalid("generals face each other".to_string());
+    }
+
+    if is_in_check(board, side_to_move.opponent()) {
+        return invalid(format!(
+            "{:?} is in check but it is {:?}'s turn",
+            side_to_move.opponent(),
+            side_to_move
+        ));
+    }
+
+    Ok(())
+}
+
+/// Finds the general of the given color.
+pub fn find_general(board: &Board, color: Color) -> Option<Square> {
+    Square::all()
+        .find(|&square| board.piece_at(square) == Some(Piece::new(PieceType::General, color)))
+}
+
+/// Returns true when both generals stand on the same file with nothing between them.
+pub fn generals_facing(board: &Board) -> bool {
+    match (
+        find_general(board, Color::Red),
+        find_general(board, Color::Black),
+    ) {
+        (Some(red), Some(black)) if red.x() == black.x() => {
+            let (red_x, red_y, black_y) = (red.x(), red.y(), black.y());
+            let (start, end) = if red_y < black_y {
+                (red_y + 1, black_y)
+            } else {
+                (black_y + 1, red_y)
+            };
+            (start..end).all(|y| board.get_piece(red_x, y).is_none())
+        }
+        _ => false,
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::items_after_test_module)]
 mod tests {
     use super::*;
     use crate::board::Board;
     use crate::piece::{Color, Piece, PieceType};
 
+    fn mv(from_x: usize, from_y: usize, to_x: usize, to_y: usize) -> Move {
+        Move::from_coords(from_x, from_y, to_x, to_y).unwrap()
+    }
+
     #[test]
     fn test_soldier_move_before_crossing_river() {
         // Red soldier before crossing river (y=6)
@@ -64,28 +234,28 @@ mod tests {
         board.set_piece(4, 6, Some(Piece::new(PieceType::Soldier, Color::Red)));
 
         // Should be able to move forward (up - toward black's territory)
-        assert!(validate_move(&board, 4, 6, 4, 5, Color::Red).is_ok());
+        assert!(validate_move(&mut board, mv(4, 6, 4, 5), Color::Red).is_ok());
 
         // Should NOT be able to move sideways
-        assert!(validate_move(&board, 4, 6, 3, 6, Color::Red).is_err());
-        assert!(validate_move(&board, 4, 6, 5, 6, Color::Red).is_err());
+        assert!(validate_move(&mut board, mv(4, 6, 3, 6), Color::Red).is_err());
+        assert!(validate_move(&mut board, mv(4, 6, 5, 6), Color::Red).is_err());
 
         // Should NOT be able to move backward (down - toward own territory)
-        assert!(validate_move(&board, 4, 6, 4, 7, Color::Red).is_err());
+        assert!(validate_move(&mut board, mv(4, 6, 4, 7), Color::Red).is_err());
 
         // Black soldier before crossing river (y=3)
         let mut board = Board::new();
         board.set_piece(4, 3, Some(Piece::new(PieceType::Soldier, Color::Black)));
 
         // Should be able to move forward (down - toward red's territory)
-        assert!(validate_move(&board, 4, 3, 4, 4, Color::Black).is_ok());
+        assert!(validate_move(&mut board, mv(4, 3, 4, 4), Color::Black).is_ok());
 
         // Should NOT be able to move sideways
-        assert!(validate_move(&board, 4, 3, 3, 3, Color::Black).is_err());
-        assert!(validate_move(&board, 4, 3, 5, 3, Color::Black).is_err());
+        assert!(validate_move(&mut board, mv(4, 3, 3, 3), Color::Black).is_err());
+        assert!(validate_move(&mut board, mv(4, 3, 5, 3), Color::Black).is_err());
 
         // Should NOT be able to move backward (up - toward own territory)
-        assert!(validate_move(&board, 4, 3, 4, 2, Color::Black).is_err());
+        assert!(validate_move(&mut board, mv(4, 3, 4, 2), Color::Black).is_err());
     }
 
     #[test]
@@ -95,28 +265,239 @@ mod tests {
         board.set_piece(4, 4, Some(Piece::new(PieceType::Soldier, Color::Red)));
 
         // Should be able to move forward (up - toward black's territory)
-        assert!(validate_move(&board, 4, 4, 4, 3, Color::Red).is_ok());
+        assert!(validate_move(&mut board, mv(4, 4, 4, 3), Color::Red).is_ok());
 
         // Should be able to move sideways
-        assert!(validate_move(&board, 4, 4, 3, 4, Color::Red).is_ok());
-        assert!(validate_move(&board, 4, 4, 5, 4, Color::Red).is_ok());
+        assert!(validate_move(&mut board, mv(4, 4, 3, 4), Color::Red).is_ok());
+        assert!(validate_move(&mut board, mv(4, 4, 5, 4), Color::Red).is_ok());
 
         // Should NOT be able to move backward (down - toward own territory)
-        assert!(validate_move(&board, 4, 4, 4, 5, Color::Red).is_err());
+        assert!(validate_move(&mut board, mv(4, 4, 4, 5), Color::Red).is_err());
 
         // Black soldier after crossing river (y=5)
         let mut board = Board::new();
         board.set_piece(4, 5, Some(Piece::new(PieceType::Soldier, Color::Black)));
 
         // Should be able to move forward (down - toward red's territory)
-        assert!(validate_move(&board, 4, 5, 4, 6, Color::Black).is_ok());
+        assert!(validate_move(&mut board, mv(4, 5, 4, 6), Color::Black).is_ok());
 
         // Should be able to move sideways
-        assert!(validate_move(&board, 4, 5, 3, 5, Color::Black).is_ok());
-        assert!(validate_move(&board, 4, 5, 5, 5, Color::Black).is_ok());
+        assert!(validate_move(&mut board, mv(4, 5, 3, 5), Color::Black).is_ok());
+        assert!(validate_move(&mut board, mv(4, 5, 5, 5), Color::Black).is_ok());
 
         // Should NOT be able to move backward (up - toward own territory)
-        assert!(validate_move(&board, 4, 5, 4, 4, Color::Black).is_err());
+        assert!(validate_move(&mut board, mv(4, 5, 4, 4), Color::Black).is_err());
+    }
+
+    #[test]
+    fn test_move_exposing_generals_is_rejected() {
+        // Red chariot is the only piece between the two generals
+        let mut board = Board::new();
+        board.set_piece(4, 0, Some(Piece::new(PieceType::General, Color::Black)));
+        board.set_piece(4, 9, Some(Piece::new(PieceType::General, Color::Red)));
+        board.set_piece(4, 5, Some(Piece::new(PieceType::Chariot, Color::Red)));
+
+        // Moving along the file keeps the generals apart
+        assert!(validate_move(&mut board, mv(4, 5, 4, 3), Color::Red).is_ok());
+
+        // Moving off the file exposes the generals
+        assert_eq!(
+            validate_move(&mut board, mv(4, 5, 3, 5), Color::Red),
+            Err(crate::ChessError::GeneralsFacing)
+        );
+
+        // Black chariot is the only piece between the two generals
+        let mut board = Board::new();
+        board.set_piece(4, 0, Some(Piece::new(PieceType::General, Color::Black)));
+        board.set_piece(4, 9, Some(Piece::new(PieceType::General, Color::Red)));
+        board.set_piece(4, 4, Some(Piece::new(PieceType::Chariot, Color::Black)));
+
+        assert!(validate_move(&mut board, mv(4, 4, 4, 6), Color::Black).is_ok());
+        assert_eq!(
+            validate_move(&mut board, mv(4, 4, 5, 4), Color::Black),
+            Err(crate::ChessError::GeneralsFacing)
+        );
+    }
+
+    #[test]
+    fn test_general_cannot_step_onto_open_file() {
+        // Red general steps onto the black general's open file
+        let mut board = Board::new();
+        board.set_piece(4, 0, Some(Piece::new(PieceType::General, Color::Black)));
+        board.set_piece(3, 9, Some(Piece::new(PieceType::General, Color::Red)));
+
+        assert_eq!(
+            validate_move(&mut board, mv(3, 9, 4, 9), Color::Red),
+            Err(crate::ChessError::GeneralsFacing)
+        );
+        assert!(validate_move(&mut board, mv(3, 9, 3, 8), Color::Red).is_ok());
+
+        // Black general steps onto the red general's open file
+        let mut board = Board::new();
+        board.set_piece(5, 0, Some(Piece::new(PieceType::General, Color::Black)));
+        board.set_piece(4, 9, Some(Piece::new(PieceType::General, Color::Red)));
+
+        assert_eq!(
+            validate_move(&mut board, mv(5, 0, 4, 0), Color::Black),
+            Err(crate::ChessError::GeneralsFacing)
+        );
+        assert!(validate_move(&mut board, mv(5, 0, 5, 1), Color::Black).is_ok());
+    }
+
+    #[test]
+    fn test_general_attacks_across_open_file() {
+        let mut board = Board::new();
+        board.set_piece(4, 0, Some(Piece::new(PieceType::General, Color::Black)));
+        board.set_piece(4, 9, Some(Piece::new(PieceType::General, Color::Red)));
+
+        assert!(generals_facing(&board));
+        assert!(validate_move(&mut board, mv(4, 9, 4, 0), Color::Red).is_ok());
+        assert!(validate_move(&mut board, mv(4, 0, 4, 9), Color::Black).is_ok());
+
+        // A piece in between blocks the attack
+        board.set_piece(4, 5, Some(Piece::new(PieceType::Horse, Color::Black)));
+        assert!(!generals_facing(&board));
+        assert!(validate_move(&mut board, mv(4, 9, 4, 0), Color::Red).is_err());
+        assert!(validate_move(&mut board, mv(4, 0, 4, 9), Color::Black).is_err());
+    }
+
+    #[test]
+    fn test_move_leaving_general_in_check_is_rejected() {
+        // Red horse shields the red general from a black chariot
+        let mut board = Board::new();
+        board.set_piece(3, 0, Some(Piece::new(PieceType::General, Color::Black)));
+        board.set_piece(4, 9, Some(Piece::new(PieceType::General, Color::Red)));
+        board.set_piece(4, 7, Some(Piece::new(PieceType::Horse, Color::Red)));
+        board.set_piece(4, 2, Some(Piece::new(PieceType::Chariot, Color::Black)));
+
+        // The horse move itself follows the piece rules...
+        assert!(validate_move(&mut board, mv(4, 7, 2, 6), Color::Red).is_ok());
+        // ...but exposes the general to the chariot
+        assert_eq!(
+            validate_legal_move(&mut board, mv(4, 7, 2, 6), Color::Red),
+            Err(crate::ChessError::InCheck)
+        );
+
+        // Black general in check must escape
+        let mut board = Board::new();
+        board.set_piece(4, 0, Some(Piece::new(PieceType::General, Color::Black)));
+        board.set_piece(3, 9, Some(Piece::new(PieceType::General, Color::Red)));
+        board.set_piece(4, 5, Some(Piece::new(PieceType::Chariot, Color::Red)));
+        board.set_piece(0, 3, Some(Piece::new(PieceType::Soldier, Color::Black)));
+
+        assert!(is_in_check(&board, Color::Black));
+        assert_eq!(
+            validate_legal_move(&mut board, mv(0, 3, 0, 4), Color::Black),
+            Err(crate::ChessError::InCheck)
+        );
+        assert!(validate_legal_move(&mut board, mv(4, 0, 5, 0), Color::Black).is_ok());
+    }
+
+    #[test]
+    fn test_general_and_advisor_stay_in_own_palace() {
+        // Red general placed inside the black palace cannot move there
+        let mut board = Board::new();
+        board.set_piece(4, 1, Some(Piece::new(PieceType::General, Color::Red)));
+        assert!(validate_move(&mut board, mv(4, 1, 4, 2), Color::Red).is_err());
+
+        // Black advisor placed inside the red palace cannot move there
+        let mut board = Board::new();
+        board.set_piece(4, 8, Some(Piece::new(PieceType::Advisor, Color::Black)));
+        assert!(validate_move(&mut board, mv(4, 8, 3, 9), Color::Black).is_err());
+
+        // Within their own palaces both move normally
+        let mut board = Board::new();
+        board.set_piece(4, 9, Some(Piece::new(PieceType::General, Color::Red)));
+        board.set_piece(3, 0, Some(Piece::new(PieceType::General, Color::Black)));
+        board.set_piece(5, 9, Some(Piece::new(PieceType::Advisor, Color::Red)));
+        board.set_piece(4, 1, Some(Piece::new(PieceType::Advisor, Color::Black)));
+        assert!(validate_move(&mut board, mv(4, 9, 4, 8), Color::Red).is_ok());
+        assert!(validate_move(&mut board, mv(5, 9, 4, 8), Color::Red).is_ok());
+        assert!(validate_move(&mut board, mv(3, 0, 3, 1), Color::Black).is_ok());
+        assert!(validate_move(&mut board, mv(4, 1, 5, 2), Color::Black).is_ok());
+
+        // The general never moves diagonally
+        assert!(validate_move(&mut board, mv(4, 9, 3, 8), Color::Red).is_err());
+        assert!(validate_move(&mut board, mv(3, 0, 4, 1), Color::Black).is_err());
+    }
+
+    #[test]
+    fn test_legal_square_tables() {
+        let red_elephant = Piece::new(PieceType::Elephant, Color::Red);
+        let black_elephant = Piece::new(PieceType::Elephant, Color::Black);
+        let red_points = [(2, 9), (6, 9), (0, 7), (4, 7), (8, 7), (2, 5), (6, 5)];
+        let black_points = [(2, 0), (6, 0), (0, 2), (4, 2), (8, 2), (2, 4), (6, 4)];
+
+        for y in 0..10 {
+            for x in 0..9 {
+                assert_eq!(
+                    is_legal_square(red_elephant, x, y),
+                    red_points.contains(&(x, y))
+                );
+                assert_eq!(
+                    is_legal_square(black_elephant, x, y),
+                    black_points.contains(&(x, y))
+                );
+            }
+        }
+
+        let red_advisor = Piece::new(PieceType::Advisor, Color::Red);
+        assert!(is_legal_square(red_advisor, 4, 8));
+        assert!(!is_legal_square(red_advisor, 4, 9));
+        assert!(!is_legal_square(red_advisor, 4, 1));
+
+        let black_soldier = Piece::new(PieceType::Soldier, Color::Black);
+        assert!(is_legal_square(black_soldier, 0, 3));
+        assert!(!is_legal_square(black_soldier, 1, 3));
+        assert!(!is_legal_square(black_soldier, 0, 2));
+        assert!(is_legal_square(black_soldier, 1, 5));
+        assert!(is_legal_square(black_soldier, 4, 9));
+    }
+
+    #[test]
+    fn test_validate_position() {
+        let mut board = Board::new();
+        board.set_piece(4, 0, Some(Piece::new(PieceType::General, Color::Black)));
+        board.set_piece(3, 9, Some(Piece::new(PieceType::General, Color::Red)));
+        board.set_piece(2, 9, Some(Piece::new(PieceType::Elephant, Color::Red)));
+        assert!(validate_position(&board, Color::Red).is_ok());
+
+        // Elephant off its seven points
+        let mut bad = board.clone();
+        bad.set_piece(3, 8, Some(Piece::new(PieceType::Elephant, Color::Red)));
+        assert!(matches!(
+            validate_position(&bad, Color::Red),
+            Err(crate::ChessError::InvalidPosition(_))
+        ));
+
+        // Advisor inside the opponent's palace
+        let mut bad = board.clone();
+        bad.set_piece(4, 1, Some(Piece::new(PieceType::Advisor, Color::Red)));
+        assert!(validate_position(&bad, Color::Red).is_err());
+
+        // Too many chariots
+        let mut bad = board.clone();
+        for x in 0..3 {
+            bad.set_piece(x, 5, Some(Piece::new(PieceType::Chariot, Color::Black)));
+        }
+        assert!(validate_position(&bad, Color::Red).is_err());
+
+        // Missing general
+        let mut bad = board.clone();
+        bad.set_piece(4, 0, None);
+        assert!(validate_position(&bad, Color::Red).is_err());
+
+        // Generals facing each other
+        let mut bad = board.clone();
+        bad.set_piece(3, 9, None);
+        bad.set_piece(4, 9, Some(Piece::new(PieceType::General, Color::Red)));
+        assert!(validate_position(&bad, Color::Red).is_err());
+
+        // Black is in check but it is Red's turn
+        let mut bad = board.clone();
+        bad.set_piece(4, 5, Some(Piece::new(PieceType::Chariot, Color::Red)));
+        assert!(validate_position(&bad, Color::Red).is_err());
+        assert!(validate_position(&bad, Color::Black).is_ok());
     }
 }
 
@@ -125,14 +506,14 @@ fn validate_general_move(
     from_y: usize,
     to_x: usize,
     to_y: usize,
+    color: Color,
 ) -> Result<(), crate::ChessError> {
-    // General moves one step in any direction within the palace (3x3 area)
-    let palace_x = (3..=5).contains(&from_x) && (3..=5).contains(&to_x);
-    let palace_y_red = (0..=2).contains(&from_y) && (0..=2).contains(&to_y);
-    let palace_y_black = (7..=9).contains(&from_y) && (7..=9).contains(&to_y);
-    let in_palace = palace_x && (palace_y_red || palace_y_black);
-    let one_step = (from_x as isize - to_x as isize).abs() <= 1
-        && (from_y as isize - to_y as isize).abs() <= 1;
+    // General moves one step orthogonally within its own palace
+    let general = Piece::new(PieceType::General, color);
+    let in_palace =
+        is_legal_square(general, from_x, from_y) && is_legal_square(general, to_x, to_y);
+    let one_step =
+        (from_x as isize - to_x as isize).abs() + (from_y as isize - to_y as isize).abs() == 1;
 
     if in_palace && one_step {
         Ok(())
@@ -141,17 +522,37 @@ fn validate_general_move(
     }
 }
 
+fn validate_flying_general(
+    board: &Board,
+    from_x: usize,
+    from_y: usize,
+    to_x: usize,
+    to_y: usize,
+) -> Result<(), crate::ChessError> {
+    // A general "flies" along an open file to capture the enemy general
+    let target_is_general = matches!(
+        board.get_piece(to_x, to_y),
+        Some(piece) if piece.piece_type == PieceType::General
+    );
+
+    if target_is_general && from_x == to_x {
+        validate_chariot_move(board, from_x, from_y, to_x, to_y)
+    } else {
+        Err(crate::ChessError::InvalidMove)
+    }
+}
+
 fn validate_advisor_move(
     from_x: usize,
     from_y: usize,
     to_x: usize,
     to_y: usize,
+    color: Color,
 ) -> Result<(), crate::ChessError> {
-    // Advisor moves one step diagonally within the palace
-    let palace_x = (3..=5).contains(&from_x) && (3..=5).contains(&to_x);
-    let palace_y_red = (0..=2).contains(&from_y) && (0..=2).contains(&to_y);
-    let palace_y_black = (7..=9).contains(&from_y) && (7..=9).contains(&to_y);
-    let in_palace = palace_x && (palace_y_red || palace_y_black);
+    // Advisor moves one step diagonally between the five points of its own palace
+    let advisor = Piece::new(PieceType::Advisor, color);
+    let in_palace =
+        is_legal_square(advisor, from_x, from_y) && is_legal_square(advisor, to_x, to_y);
     let diagonal = (from_x as isize - to_x as isize).abs() == 1
         && (from_y as isize - to_y as isize).abs() == 1;
 
@@ -170,18 +571,17 @@ fn validate_elephant_move(
     to_y: usize,
     color: Color,
 ) -> Result<(), crate::ChessError> {
-    // Elephant moves two steps diagonally, cannot cross river
+    // Elephant moves two steps diagonally between its seven points, cannot cross river
     let dx = (from_x as isize - to_x as isize).abs();
     let dy = (from_y as isize - to_y as isize).abs();
     let two_steps_diagonal = dx == 2 && dy == 2;
 
-    // Check if crossing river
-    let crosses_river = match color {
-        Color::Red => to_y <= 4,   // 红方象不能过河到 y <= 4（黑方半场）
-        Color::Black => to_y >= 5, // 黑方象不能过河到 y >= 5（红方半场）
-    };
+    // The seven points all lie on the elephant's own side of the river
+    let elephant = Piece::new(PieceType::Elephant, color);
+    let on_own_points =
+        is_legal_square(elephant, from_x, from_y) && is_legal_square(elephant, to_x, to_y);
 
-    if two_steps_diagonal && !crosses_river {
+    if two_steps_diagonal && on_own_points {
         // Check if eye is blocked
         let eye_x = (from_x + to_x) / 2;
         let eye_y = (from_y + to_y) / 2;
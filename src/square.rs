@@ -0,0 +1,102 @@
+use crate::ChessError;
+
+/// A square on the 9x10 board, stored as `y * 9 + x` in 0..90.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Square(u8);
+
+impl Square {
+    pub const COUNT: usize = 90;
+
+    /// Creates a square from its index, or None when the index is off the board.
+    pub fn new(index: usize) -> Option<Self> {
+        if index < Self::COUNT {
+            Some(Self(index as u8))
+        } else {
+            None
+        }
+    }
+
+    /// Creates a square from (x, y), or None when it lies off the board.
+    pub fn from_xy(x: usize, y: usize) -> Option<Self> {
+        if x < 9 && y < 10 {
+            Some(Self((y * 9 + x) as u8))
+        } else {
+            None
+        }
+    }
+
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    pub fn x(self) -> usize {
+        self.index() % 9
+    }
+
+    pub fn y(self) -> usize {
+        self.index() / 9
+    }
+
+    pub fn to_xy(self) -> (usize, usize) {
+        (self.x(), self.y())
+    }
+
+    /// Returns the square shifted by (dx, dy), or None when it leaves the board.
+    pub fn offset(self, dx: isize, dy: isize) -> Option<Self> {
+        let x = self.x() as isize + dx;
+        let y = self.y() as isize + dy;
+        if (0..9).contains(&x) && (0..10).contains(&y) {
+            Self::from_xy(x as usize, y as usize)
+        } else {
+            None
+        }
+    }
+
+    /// Iterates over all 90 squares, row by row from the top.
+    pub fn all() -> impl Iterator<Item = Square> {
+        (0..Self::COUNT as u8).map(Self)
+    }
+}
+
+impl TryFrom<(usize, usize)> for Square {
+    type Error = ChessError;
+
+    fn try_from((x, y): (usize, usize)) -> Result<Self, Self::Error> {
+        Self::from_xy(x, y).ok_or(ChessError::OutOfBoard)
+    }
+}
+
+impl From<Square> for (usize, usize) {
+    fn from(square: Square) -> Self {
+        square.to_xy()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_square_conversions() {
+        let square = Square::from_xy(4, 9).unwrap();
+        assert_eq!(square.index(), 85);
+        assert_eq!(square.to_xy(), (4, 9));
+        assert_eq!(Square::new(85), Some(square));
+        assert_eq!(Square::try_from((4, 9)), Ok(square));
+
+        assert_eq!(Square::from_xy(9, 0), None);
+        assert_eq!(Square::from_xy(0, 10), None);
+        assert_eq!(Square::new(90), None);
+        assert_eq!(Square::try_from((9, 9)), Err(ChessError::OutOfBoard));
+    }
+
+    #[test]
+    fn test_square_offset() {
+        let corner = Square::from_xy(0, 0).unwrap();
+        assert_eq!(corner.offset(-1, 0), None);
+        assert_eq!(corner.offset(0, -1), None);
+        assert_eq!(corner.offset(2, 1), Square::from_xy(2, 1));
+        assert_eq!(Square::from_xy(8, 9).unwrap().offset(1, 0), None);
+        assert_eq!(Square::all().count(), 90);
+    }
+}
@@ -1,5 +1,13 @@
+use crate::annotation::Annotation;
+use crate::dhtmlxq::{self, DhtmlXqGame};
 use crate::game::GameStateManager;
 use crate::game_with_history::GameStateWithHistory;
+use crate::moves::Move;
+use crate::notation::MoveFormat;
+use crate::pgn::{self, PgnGame};
+use crate::piece::Color;
+use crate::repetition::RuleSet;
+use crate::tree::{GameTree, NodeId};
 use crate::ChessError;
 use std::sync::Mutex;
 use tauri::command;
@@ -20,6 +28,19 @@ pub fn make_move(
     ))
 }
 
+#[command(rename_all = "camelCase")]
+pub fn make_move_iccs(
+    manager: tauri::State<'_, Mutex<GameStateManager>>,
+    iccs: String,
+) -> Result<GameStateWithHistory, ChessError> {
+    let mut manager = manager.lock().unwrap();
+    manager.play_move(Move::from_iccs(&iccs)?)?;
+    Ok(GameStateWithHistory::new(
+        manager.state.clone(),
+        manager.history.clone(),
+    ))
+}
+
 #[command(rename_all = "camelCase")]
 pub fn undo_move(
     manager: tauri::State<'_, Mutex<GameStateManager>>,
@@ -32,6 +53,18 @@ pub fn undo_move(
     ))
 }
 
+#[command(rename_all = "camelCase")]
+pub fn redo_move(
+    manager: tauri::State<'_, Mutex<GameStateManager>>,
+) -> Result<GameStateWithHistory, ChessError> {
+    let mut manager = manager.lock().unwrap();
+    manager.redo_move()?;
+    Ok(GameStateWithHistory::new(
+        manager.state.clone(),
+        manager.history.clone(),
+    ))
+}
+
 #[command(rename_all = "camelCase")]
 pub fn get_valid_moves(
     manager: tauri::State<'_, Mutex<GameStateManager>>,
@@ -41,6 +74,26 @@ pub fn get_valid_moves(
     manager.lock().unwrap().get_valid_moves(x, y)
 }
 
+#[command(rename_all = "camelCase")]
+pub fn get_attackers(
+    manager: tauri::State<'_, Mutex<GameStateManager>>,
+    x: usize,
+    y: usize,
+    color: Color,
+) -> Vec<(usize, usize)> {
+    manager.lock().unwrap().get_attackers(x, y, color)
+}
+
+#[command(rename_all = "camelCase")]
+pub fn get_checkers(manager: tauri::State<'_, Mutex<GameStateManager>>) -> Vec<(usize, usize)> {
+    manager.lock().unwrap().get_checkers()
+}
+
+#[command(rename_all = "camelCase")]
+pub fn get_move_list(manager: tauri::State<'_, Mutex<GameStateManager>>) -> Vec<String> {
+    manager.lock().unwrap().chinese_moves()
+}
+
 #[command(rename_all = "camelCase")]
 pub fn get_game_state(manager: tauri::State<'_, Mutex<GameStateManager>>) -> GameStateWithHistory {
     let manager = manager.lock().unwrap();
@@ -50,7 +103,221 @@ pub fn get_game_state(manager: tauri::State<'_, Mutex<GameStateManager>>) -> Gam
 #[command(rename_all = "camelCase")]
 pub fn new_game(manager: tauri::State<'_, Mutex<GameStateManager>>) -> GameStateWithHistory {
     let mut manager = manager.lock().unwrap();
-    manager.state = crate::game::GameState::new();
-    manager.history.clear();
+    let rule_set = manager.rule_set;
+    *manager = GameStateManager::new().with_rule_set(rule_set);
     GameStateWithHistory::new(manager.state.clone(), manager.history.clone())
 }
+
+#[command(rename_all = "camelCase")]
+pub fn get_rule_set(manager: tauri::State<'_, Mutex<GameStateManager>>) -> RuleSet {
+    manager.lock().unwrap().rule_set
+}
+
+#[command(rename_all = "camelCase")]
+pub fn set_rule_set(
+    manager: tauri::State<'_, Mutex<GameStateManager>>,
+    rule_set: RuleSet,
+) -> GameStateWithHistory {
+    let mut manager = manager.lock().unwrap();
+    manager.set_rule_set(rule_set);
+    GameStateWithHistory::new(manager.state.clone(), manager.history.clone())
+}
+
+#[command(rename_all = "camelCase")]
+pub fn get_fen(manager: tauri::State<'_, Mutex<GameStateManager>>) -> String {
+    manager.lock().unwrap().state.to_fen()
+}
+
+#[command(rename_all = "camelCase")]
+pub fn load_fen(
+    manager: tauri::State<'_, Mutex<GameStateManager>>,
+    fen: String,
+) -> Result<GameStateWithHistory, ChessError> {
+    let mut manager = manager.lock().unwrap();
+    let rule_set = manager.rule_set;
+    *manager = GameStateManager::from_fen(&fen)?.with_rule_set(rule_set);
+    Ok(GameStateWithHistory::new(
+        manager.state.clone(),
+        manager.history.clone(),
+    ))
+}
+
+#[command(rename_all = "camelCase")]
+pub fn export_pgn(
+    manager: tauri::State<'_, Mutex<GameStateManager>>,
+    format: MoveFormat,
+) -> String {
+    PgnGame::from_manager(&manager.lock().unwrap(), format).to_string()
+}
+
+#[command(rename_all = "camelCase")]
+pub fn load_pgn(
+    manager: tauri::State<'_, Mutex<GameStateManager>>,
+    pgn: String,
+) -> Result<GameStateWithHistory, ChessError> {
+    let mut manager = manager.lock().unwrap();
+    let rule_set = manager.rule_set;
+    *manager = pgn::parse(&pgn)?.to_manager()?.with_rule_set(rule_set);
+    Ok(GameStateWithHistory::new(
+        manager.state.clone(),
+        manager.history.clone(),
+    ))
+}
+
+#[command(rename_all = "camelCase")]
+pub fn load_xqf(
+    manager: tauri::State<'_, Mutex<GameStateManager>>,
+    data: Vec<u8>,
+) -> Result<GameStateWithHistory, ChessError> {
+    let mut manager = manager.lock().unwrap();
+    let rule_set = manager.rule_set;
+    *manager = GameStateManager::from_xqf(&data)?.with_rule_set(rule_set);
+    Ok(GameStateWithHistory::new(
+        manager.state.clone(),
+        manager.history.clone(),
+    ))
+}
+
+#[command(rename_all = "camelCase")]
+pub fn export_dhtmlxq(manager: tauri::State<'_, Mutex<GameStateManager>>) -> String {
+    DhtmlXqGame::from_manager(&manager.lock().unwrap()).to_string()
+}
+
+#[command(rename_all = "camelCase")]
+pub fn load_dhtmlxq(
+    manager: tauri::State<'_, Mutex<GameStateManager>>,
+    text: String,
+) -> Result<GameStateWithHistory, ChessError> {
+    let mut manager = manager.lock().unwrap();
+    let rule_set = manager.rule_set;
+    *manager = dhtmlxq::parse(&text)?.to_manager()?.with_rule_set(rule_set);
+    Ok(GameStateWithHistory::new(
+        manager.state.clone(),
+        manager.history.clone(),
+    ))
+}
+
+#[command(rename_all = "camelCase")]
+pub fn get_game_tree(manager: tauri::State<'_, Mutex<GameStateManager>>) -> GameTree {
+    manager.lock().unwrap().tree.clone()
+}
+
+#[command(rename_all = "camelCase")]
+pub fn get_current_node(manager: tauri::State<'_, Mutex<GameStateManager>>) -> NodeId {
+    manager.lock().unwrap().current_node()
+}
+
+#[command(rename_all = "camelCase")]
+pub fn goto_node(
+    manager: tauri::State<'_, Mutex<GameStateManager>>,
+    node: NodeId,
+) -> Result<GameStateWithHistory, ChessError> {
+    let mut manager = manager.lock().unwrap();
+    manager.goto_node(node)?;
+    Ok(GameStateWithHistory::new(
+        manager.state.clone(),
+        manager.history.clone(),
+    ))
+}
+
+#[command(rename_all = "camelCase")]
+pub fn promote_variation(
+    manager: tauri::State<'_, Mutex<GameStateManager>>,
+    node: NodeId,
+) -> Result<GameTree, ChessError> {
+    let mut manager = manager.lock().unwrap();
+    manager.promote_variation(node)?;
+    Ok(manager.tree.clone())
+}
+
+#[command(rename_all = "camelCase")]
+pub fn demote_variation(
+    manager: tauri::State<'_, Mutex<GameStateManager>>,
+    node: NodeId,
+) -> Result<GameTree, ChessError> {
+    let mut manager = manager.lock().unwrap();
+    manager.demote_variation(node)?;
+    Ok(manager.tree.clone())
+}
+
+#[command(rename_all = "camelCase")]
+pub fn goto_ply(
+    manager: tauri::State<'_, Mutex<GameStateManager>>,
+    ply: usize,
+) -> Result<GameStateWithHistory, ChessError> {
+    let mut manager = manager.lock().unwrap();
+    manager.goto_ply(ply)?;
+    Ok(GameStateWithHistory::new(
+        manager.state.clone(),
+        manager.history.clone(),
+    ))
+}
+
+/// The ply on the board and the number of plies in the current line.
+#[command(rename_all = "camelCase")]
+pub fn get_replay_position(manager: tauri::State<'_, Mutex<GameStateManager>>) -> (usize, usize) {
+    let manager = manager.lock().unwrap();
+    (manager.current_ply(), manager.current_line().len())
+}
+
+#[command(rename_all = "camelCase")]
+pub fn goto_first(
+    manager: tauri::State<'_, Mutex<GameStateManager>>,
+) -> Result<GameStateWithHistory, ChessError> {
+    let mut manager = manager.lock().unwrap();
+    manager.goto_first()?;
+    Ok(GameStateWithHistory::new(
+        manager.state.clone(),
+        manager.history.clone(),
+    ))
+}
+
+#[command(rename_all = "camelCase")]
+pub fn goto_last(
+    manager: tauri::State<'_, Mutex<GameStateManager>>,
+) -> Result<GameStateWithHistory, ChessError> {
+    let mut manager = manager.lock().unwrap();
+    manager.goto_last()?;
+    Ok(GameStateWithHistory::new(
+        manager.state.clone(),
+        manager.history.clone(),
+    ))
+}
+
+#[command(rename_all = "camelCase")]
+pub fn goto_previous(
+    manager: tauri::State<'_, Mutex<GameStateManager>>,
+) -> Result<GameStateWithHistory, ChessError> {
+    let mut manager = manager.lock().unwrap();
+    manager.goto_previous()?;
+    Ok(GameStateWithHistory::new(
+        manager.state.clone(),
+        manager.history.clone(),
+    ))
+}
+
+#[command(rename_all = "camelCase")]
+pub fn goto_next(
+    manager: tauri::State<'_, Mutex<GameStateManager>>,
+) -> Result<GameStateWithHistory, ChessError> {
+    let mut manager = manager.lock().unwrap();
+    manager.goto_next()?;
+    Ok(GameStateWithHistory::new(
+        manager.state.clone(),
+        manager.history.clone(),
+    ))
+}
+
+#[command(rename_all = "camelCase")]
+pub fn annotate_move(
+    manager: tauri::State<'_, Mutex<GameStateManager>>,
+    node: NodeId,
+    annotation: Annotation,
+) -> Result<GameStateWithHistory, ChessError> {
+    let mut manager = manager.lock().unwrap();
+    manager.annotate_move(node, annotation)?;
+    Ok(GameStateWithHistory::new(
+        manager.state.clone(),
+        manager.history.clone(),
+    ))
+}
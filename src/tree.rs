@@ -0,0 +1,584 @@
+use crate::annotation::Annotation;
+use crate::game::GameStateManager;
+use crate::history::{History, MoveRecord};
+use crate::ChessError;
+use serde::{Deserialize, Serialize};
+
+/// Index of a node in a `GameTree`.
+pub type NodeId = usize;
+
+/// The start position, root of every tree.
+pub const ROOT: NodeId = 0;
+
+/// A position reached in the game, identified by the move leading to it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TreeNode {
+    /// The move leading here, `None` at the root
+    pub record: Option<MoveRecord>,
+    pub parent: Option<NodeId>,
+    /// Continuations, the main line first
+    pub children: Vec<NodeId>,
+}
+
+/// Every line played from the start position. The main line follows the
+/// first child of every node; the other children start side variations.
+/// Nodes are never removed, so a `NodeId` stays valid for the whole game.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GameTree {
+    nodes: Vec<TreeNode>,
+    /// Comment on the start position, before the first move
+    #[serde(default)]
+    comment: Option<String>,
+}
+
+impl GameTree {
+    pub fn new() -> Self {
+        Self {
+            nodes: vec![TreeNode {
+                record: None,
+                parent: None,
+                children: Vec::new(),
+            }],
+            comment: None,
+        }
+    }
+
+    /// Number of nodes, the root included.
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn contains(&self, node: NodeId) -> bool {
+        node < self.nodes.len()
+    }
+
+    pub fn node(&self, node: NodeId) -> Option<&TreeNode> {
+        self.nodes.get(node)
+    }
+
+    /// The move leading to a node.
+    pub fn record(&self, node: NodeId) -> Option<&MoveRecord> {
+        self.nodes.get(node)?.record.as_ref()
+    }
+
+    pub fn parent(&self, node: NodeId) -> Option<NodeId> {
+        self.nodes.get(node)?.parent
+    }
+
+    /// The main continuation of a node.
+    pub fn next(&self, node: NodeId) -> Option<NodeId> {
+        self.children(node).first().copied()
+    }
+
+    pub fn children(&self, node: NodeId) -> &[NodeId] {
+        self.nodes
+            .get(node)
+            .map_or(&[], |node| node.children.as_slice())
+    }
+
+    /// The node and its alternatives, in order, the main one first.
+    pub fn siblings(&self, node: NodeId) -> &[NodeId] {
+        match self.parent(node) {
+            Some(parent) => self.children(parent),
+            None => std::slice::from_ref(&ROOT),
+        }
+    }
+
+    /// Number of moves from the start position to a node.
+    pub fn ply(&self, node: NodeId) -> usize {
+        self.path(node).len()
+    }
+
+    /// The nodes from the first move up to and including `node`.
+    pub fn path(&self, node: NodeId) -> Vec<NodeId> {
+        let mut path = Vec::new();
+        let mut current = Some(node).filter(|&node| self.contains(node));
+        while let Some(node) = current.filter(|&node| node != ROOT) {
+            path.push(node);
+            current = self.parent(node);
+        }
+        path.reverse();
+        path
+    }
+
+    /// The nodes of the main line, from the first move on.
+    pub fn main_line(&self) -> Vec<NodeId> {
+        let mut line = Vec::new();
+        let mut current = ROOT;
+        while let Some(next) = self.next(current) {
+            line.push(next);
+            current = next;
+        }
+        line
+    }
+
+    /// Returns true when the node lies on the main line.
+    pub fn is_main_line(&self, node: NodeId) -> bool {
+        self.contains(node)
+            && self
+                .path(node)
+                .iter()
+                .all(|&node| self.siblings(node)[0] == node)
+    }
+
+    /// The moves along a path, grouped into rounds.
+    pub fn history(&self, path: &[NodeId]) -> History {
+        let mut history = History::new();
+        for record in path.iter().filter_map(|&node| self.record(node)) {
+            history.push_with_color(record.clone(), record.piece.color);
+        }
+        history
+    }
+
+    /// The main line grouped into rounds, as shown in the history panel.
+    pub fn main_line_history(&self) -> History {
+        self.history(&self.main_line())
+    }
+
+    /// The comment before the first move.
+    pub fn comment(&self) -> Option<&str> {
+        self.comment.as_deref()
+    }
+
+    /// Replaces the comment before the first move; an empty one removes it.
+    pub fn set_comment(&mut self, comment: Option<String>) {
+        self.comment = comment.filter(|comment| !comment.is_empty());
+    }
+
+    /// Adds a move after `parent` and returns its node. A move that was
+    /// already played there is not added twice; a new one becomes the main
+    /// continuation if there is none yet, a variation otherwise.
+    pub fn add_move(&mut self, parent: NodeId, record: MoveRecord) -> NodeId {
+        let existing = self.children(parent).iter().copied().find(|&child| {
+            self.record(child)
+                .is_some_and(|known| known.mv() == record.mv())
+        });
+        if let Some(child) = existing {
+            return child;
+        }
+
+        let node = self.nodes.len();
+        self.nodes.push(TreeNode {
+            record: Some(record),
+            parent: Some(parent),
+            children: Vec::new(),
+        });
+        self.nodes[parent].children.push(node);
+        node
+    }
+
+    /// Replaces the annotation of the move leading to a node. Returns false
+    /// for the root and unknown nodes.
+    pub fn annotate(&mut self, node: NodeId, annotation: Annotation) -> bool {
+        match self
+            .nodes
+            .get_mut(node)
+            .and_then(|node| node.record.as_mut())
+        {
+            Some(record) => {
+                record.annotation = annotation;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Moves a variation one place up among its siblings, making it the main
+    /// continuation when it was the first variation. Returns false when it
+    /// already comes first.
+    pub fn promote(&mut self, node: NodeId) -> bool {
+        self.shift(node, -1)
+    }
+
+    /// Moves a node one place down among its siblings. Returns false when it
+    /// already comes last.
+    pub fn demote(&mut self, node: NodeId) -> bool {
+        self.shift(node, 1)
+    }
+
+    /// Promotes the node and every node leading to it to the front, so the
+    /// main line passes through it.
+    pub fn make_main_line(&mut self, node: NodeId) {
+        for node in self.path(node) {
+            while self.promote(node) {}
+        }
+    }
+
+    fn shift(&mut self, node: NodeId, offset: isize) -> bool {
+        let Some(parent) = self.parent(node) else {
+            return false;
+        };
+        let children = &mut self.nodes[parent].children;
+        let index = children.iter().position(|&child| child == node).unwrap();
+        match index.checked_add_signed(offset) {
+            Some(target) if target < children.len() => {
+                children.swap(index, target);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Default for GameTree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GameStateManager {
+    /// The node of the position on the board.
+    pub fn current_node(&self) -> NodeId {
+        self.current
+    }
+
+    /// Moves to any node of the tree, taking back moves to the last node it
+    /// shares with the current line and replaying the moves after it. Moves
+    /// taken back before can still be redone afterwards.
+    pub fn goto_node(&mut self, node: NodeId) -> Result<(), ChessError> {
+        if !self.tree.contains(node) {
+            return Err(ChessError::UnknownNode);
+        }
+
+        let target = self.tree.path(node);
+        let current = self.tree.path(self.current);
+        let shared = target
+            .iter()
+            .zip(&current)
+            .take_while(|(a, b)| a == b)
+            .count();
+
+        self.navigate(current.len() - shared, &target[shared..])?;
+        debug_assert_eq!(self.current, node);
+        Ok(())
+    }
+
+    // Takes back `back` moves, then plays the moves leading to `forward` in
+    // turn. The positions passed on the way are not judged, so a line that
+    // went on past a repetition can be followed; the one reached is, unless
+    // its result is already known. If a step fails, the game is put back
+    // where it was.
+    fn navigate(&mut self, back: usize, forward: &[NodeId]) -> Result<(), ChessError> {
+        let start = self.clone();
+        match self.walk(back, forward) {
+            Ok(judged) => {
+                if !judged && !self.state.is_ended {
+                    self.update_status();
+                }
+                Ok(())
+            }
+            Err(err) => {
+                *self = start;
+                Err(err)
+            }
+        }
+    }
+
+    // The steps of `navigate`; returns whether the result of the position
+    // reached is known
+    fn walk(&mut self, back: usize, forward: &[NodeId]) -> Result<bool, ChessError> {
+        for _ in 0..back {
+            self.undo_move()?;
+        }
+        let mut judged = true;
+        for &node in forward {
+            judged = self.replay_node(node)?;
+        }
+        Ok(judged)
+    }
+
+    /// The line through the position on the board: the moves leading to it,
+    /// then at each step the move last taken back from there, or else the
+    /// main continuation.
+    pub fn current_line(&self) -> Vec<NodeId> {
+        let mut line = self.tree.path(self.current);
+        let mut node = self.current;
+        while let Some(next) = self.redo_target(node).or_else(|| self.tree.next(node)) {
+            line.push(next);
+            node = next;
+        }
+        line
+    }
+
+    /// Number of moves played from the start position to the board.
+    pub fn current_ply(&self) -> usize {
+        self.tree.ply(self.current)
+    }
+
+    /// Shows the position after `ply` moves of the current line, 0 being the
+    /// start position, whether it was set up from FEN or not. The position
+    /// is rebuilt by taking back or replaying recorded moves, so no move of
+    /// the line is lost and stepping forward again restores every capture
+    /// and the result the game had.
+    pub fn goto_ply(&mut self, ply: usize) -> Result<(), ChessError> {
+        let line = self.current_line();
+        if ply > line.len() {
+            return Err(ChessError::UnknownNode);
+        }
+
+        let here = self.current_ply();
+        if ply < here {
+            self.navigate(here - ply, &[])
+        } else {
+            self.navigate(0, &line[here..ply])
+        }
+    }
+
+    /// Shows the start position.
+    pub fn goto_first(&mut self) -> Result<(), ChessError> {
+        self.goto_ply(0)
+    }
+
+    /// Shows the position at the end of the current line.
+    pub fn goto_last(&mut self) -> Result<(), ChessError> {
+        self.goto_ply(self.current_line().len())
+    }
+
+    /// Steps one move back along the current line.
+    pub fn goto_previous(&mut self) -> Result<(), ChessError> {
+        let ply = self.current_ply().checked_sub(1);
+        self.goto_ply(ply.ok_or(ChessError::NoHistory)?)
+    }
+
+    /// Steps one move forward along the current line.
+    pub fn goto_next(&mut self) -> Result<(), ChessError> {
+        let ply = self.current_ply() + 1;
+        if ply > self.current_line().len() {
+            return Err(ChessError::NothingToRedo);
+        }
+        self.goto_ply(ply)
+    }
+
+    /// Moves a variation one place up among its siblings.
+    pub fn promote_variation(&mut self, node: NodeId) -> Result<(), ChessError> {
+        if !self.tree.contains(node) {
+            return Err(ChessError::UnknownNode);
+        }
+        self.tree.promote(node);
+        Ok(())
+    }
+
+    /// Moves a variation one place down among its siblings.
+    pub fn demote_variation(&mut self, node: NodeId) -> Result<(), ChessError> {
+        if !self.tree.contains(node) {
+            return Err(ChessError::UnknownNode);
+        }
+        self.tree.demote(node);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::moves::Move;
+    use crate::piece::{Color, Piece, PieceType};
+
+    fn play(manager: &mut GameStateManager, moves: &[&str]) {
+        for text in moves {
+            manager.play_move(Move::from_iccs(text).unwrap()).unwrap();
+        }
+    }
+
+    fn iccs(tree: &GameTree, nodes: &[NodeId]) -> Vec<String> {
+        nodes
+            .iter()
+            .map(|&node| tree.record(node).unwrap().mv().to_iccs())
+            .collect()
+    }
+
+    #[test]
+    fn test_undo_keeps_the_line() {
+        let mut manager = GameStateManager::new();
+        play(&mut manager, &["h2e2", "h9g7", "h0g2"]);
+        manager.undo_move().unwrap();
+        manager.undo_move().unwrap();
+
+        // Black tries a different answer, which becomes a variation
+        play(&mut manager, &["b7e7"]);
+        let tree = &manager.tree;
+        assert_eq!(iccs(tree, &tree.main_line()), ["h2e2", "h9g7", "h0g2"]);
+        assert_eq!(iccs(tree, tree.children(1)), ["h9g7", "b7e7"]);
+        assert_eq!(tree.siblings(manager.current_node()), [2, 4]);
+        assert!(!tree.is_main_line(manager.current_node()));
+        assert_eq!(manager.history, tree.history(&[1, 4]));
+
+        // Replaying a known move reuses its node
+        manager.undo_move().unwrap();
+        play(&mut manager, &["h9g7"]);
+        assert_eq!(manager.current_node(), 2);
+        assert_eq!(manager.tree.node_count(), 5);
+        assert_eq!(manager.tree.next(2), Some(3));
+        assert_eq!(manager.tree.parent(2), Some(1));
+        assert_eq!(manager.tree.ply(3), 3);
+    }
+
+    #[test]
+    fn test_goto_node() {
+        let mut manager = GameStateManager::new();
+        play(&mut manager, &["h2e2", "h9g7", "h0g2", "i9h9"]);
+        let main_line = manager.clone();
+        manager.goto_node(1).unwrap();
+        play(&mut manager, &["b7e7", "b0c2"]);
+        let variation = manager.clone();
+
+        // Across to the end of the main line and back into the variation
+        manager.goto_node(4).unwrap();
+        assert_eq!(manager.state, main_line.state);
+        assert_eq!(manager.history, main_line.history);
+        assert_eq!(manager.positions, main_line.positions);
+        manager.goto_node(6).unwrap();
+        assert_eq!(manager.state, variation.state);
+        assert_eq!(manager.history, variation.history);
+
+        manager.goto_node(ROOT).unwrap();
+        assert_eq!(manager.state, GameStateManager::new().state);
+        assert_eq!(manager.goto_node(7), Err(ChessError::UnknownNode));
+        assert_eq!(manager.tree.node_count(), 7);
+    }
+
+    #[test]
+    fn test_goto_ply_keeps_later_moves() {
+        let mut manager =
+            GameStateManager::from_fen("4k4/9/9/9/9/9/9/9/4A4/3K1C3 b - - 0 40").unwrap();
+        play(&mut manager, &["e9e8", "f0f8", "e8e9", "d0d1"]);
+        manager.resign(Color::Black).unwrap();
+        let finished = manager.clone();
+        let line = manager.current_line();
+
+        manager.goto_first().unwrap();
+        assert_eq!(
+            manager.state.to_fen(),
+            "4k4/9/9/9/9/9/9/9/4A4/3K1C3 b - - 0 40"
+        );
+        assert_eq!(manager.current_line(), line);
+        assert_eq!(manager.goto_previous(), Err(ChessError::NoHistory));
+
+        manager.goto_ply(3).unwrap();
+        assert_eq!(manager.current_ply(), 3);
+        assert_eq!(manager.history.moves().count(), 3);
+        manager.goto_ply(1).unwrap();
+        manager.goto_next().unwrap();
+        assert_eq!(
+            manager.state.board.get_piece(5, 1).map(|p| p.color),
+            Some(Color::Red)
+        );
+        manager.goto_previous().unwrap();
+        assert_eq!(manager.current_ply(), 1);
+        assert_eq!(manager.goto_ply(5), Err(ChessError::UnknownNode));
+
+        // The resignation comes back at the end of the line
+        manager.goto_last().unwrap();
+        assert_eq!(manager.state, finished.state);
+        assert_eq!(manager.history, finished.history);
+        assert_eq!(manager.goto_next(), Err(ChessError::NothingToRedo));
+    }
+
+    #[test]
+    fn test_goto_ply_follows_the_main_continuation() {
+        let mut manager = GameStateManager::new();
+        play(&mut manager, &["h2e2", "h9g7", "h0g2"]);
+        manager.goto_node(1).unwrap();
+        play(&mut manager, &["b7e7"]);
+
+        // From the variation the line continues through its own moves only
+        assert_eq!(manager.current_line(), [1, 4]);
+        assert_eq!(manager.goto_next(), Err(ChessError::NothingToRedo));
+
+        // Going back keeps the variation as the line to step through
+        manager.goto_first().unwrap();
+        assert_eq!(manager.current_line(), [1, 4]);
+
+        // Entering the main line switches to it, up to its end
+        manager.goto_node(2).unwrap();
+        assert_eq!(manager.current_line(), [1, 2, 3]);
+        manager.goto_first().unwrap();
+        manager.goto_last().unwrap();
+        assert_eq!(manager.current_node(), 3);
+        assert_eq!(manager.tree.node_count(), 5);
+    }
+
+    #[test]
+    fn test_navigation_keeps_moves_taken_back() {
+        let mut manager = GameStateManager::new();
+        play(&mut manager, &["h2e2", "h9g7", "h0g2", "i9h9"]);
+        let finished = manager.clone();
+        manager.undo_move().unwrap();
+        manager.undo_move().unwrap();
+        let horse = Piece::new(PieceType::Horse, Color::Red);
+        let variation = manager.tree.add_move(
+            2,
+            MoveRecord::new(Move::from_iccs("b0c2").unwrap(), horse, None),
+        );
+
+        // Looking at a variation does not drop the moves taken back
+        manager.goto_node(variation).unwrap();
+        assert_eq!(manager.redo_target(2), Some(3));
+        assert_eq!(manager.redo_move(), Err(ChessError::NothingToRedo));
+
+        manager.goto_node(4).unwrap();
+        assert_eq!(manager.state, finished.state);
+        assert_eq!(manager.history, finished.history);
+        assert_eq!(manager.redo_target(2), Some(variation));
+    }
+
+    #[test]
+    fn test_failed_navigation_changes_nothing() {
+        let mut manager = GameStateManager::new();
+        play(&mut manager, &["h2e2", "h9g7"]);
+        manager.undo_move().unwrap();
+
+        // A chariot jumping over its own pieces cannot be replayed
+        let chariot = Piece::new(PieceType::Chariot, Color::Red);
+        let broken = manager.tree.add_move(
+            2,
+            MoveRecord::new(Move::from_iccs("a0a9").unwrap(), chariot, None),
+        );
+        let before = manager.clone();
+        assert_eq!(manager.goto_node(broken), Err(ChessError::InvalidMove));
+        assert_eq!(manager.state, before.state);
+        assert_eq!(manager.history, before.history);
+        assert_eq!(manager.positions, before.positions);
+        assert_eq!(manager.current_node(), 1);
+        assert_eq!(manager.redo_stack, before.redo_stack);
+        manager.redo_move().unwrap();
+        assert_eq!(manager.current_node(), 2);
+    }
+
+    #[test]
+    fn test_promote_and_demote() {
+        let mut manager = GameStateManager::new();
+        play(&mut manager, &["h2e2", "h9g7"]);
+        for answer in ["b7e7", "b9c7"] {
+            manager.goto_node(1).unwrap();
+            play(&mut manager, &[answer]);
+        }
+        play(&mut manager, &["h0g2"]);
+        assert_eq!(manager.tree.children(1), [2, 3, 4]);
+
+        assert!(manager.tree.promote(4));
+        assert_eq!(manager.tree.children(1), [2, 4, 3]);
+        assert!(manager.tree.demote(2));
+        assert_eq!(manager.tree.children(1), [4, 2, 3]);
+        assert!(!manager.tree.promote(4));
+        assert!(!manager.tree.demote(3));
+        assert!(!manager.tree.promote(ROOT));
+        assert_eq!(
+            iccs(&manager.tree, &manager.tree.main_line()),
+            ["h2e2", "b9c7", "h0g2"]
+        );
+
+        manager.tree.make_main_line(3);
+        assert_eq!(manager.tree.main_line(), [1, 3]);
+        assert_eq!(manager.tree.main_line_history().len(), 1);
+        assert!(manager.tree.is_main_line(3));
+
+        // Reordering never moves the board
+        let before = manager.state.clone();
+        manager.demote_variation(3).unwrap();
+        manager.promote_variation(5).unwrap();
+        assert_eq!(manager.state, before);
+        assert_eq!(manager.current_node(), 5);
+        assert_eq!(manager.promote_variation(9), Err(ChessError::UnknownNode));
+    }
+}
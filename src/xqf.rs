@@ -0,0 +1,656 @@
+use crate::annotation::Annotation;
+use crate::board::Board;
+use crate::game::{GameState, GameStateManager};
+use crate::moves::Move;
+use crate::piece::{Color, Piece, PieceType};
+use crate::rules;
+use crate::square::Square;
+use crate::tree::ROOT;
+use crate::ChessError;
+use encoding_rs::GB18030;
+
+// XQF is the binary format of XQStudio. A 1024 byte header holds the game
+// information and the 32 piece squares, followed by the move tree as
+// records in pre-order: each record flags whether its continuation and an
+// alternative to it follow. Version 1.1 and later files are encrypted with
+// keys stored in the header.
+
+const HEADER_LEN: usize = 1024;
+
+// Header offsets; text fields are length-prefixed GB18030 strings
+const VERSION: usize = 2;
+const KEY_MASK: usize = 3;
+const KEY_OR: usize = 8;
+const KEY_SUM: usize = 12;
+const KEY_XY: usize = 13;
+const KEY_XY_FROM: usize = 14;
+const KEY_XY_TO: usize = 15;
+const PIECES: usize = 16;
+const WHO_PLAYS: usize = 50;
+const RESULT: usize = 51;
+const TITLE: (usize, usize) = (80, 64);
+const EVENT: (usize, usize) = (208, 64);
+const DATE: (usize, usize) = (272, 16);
+const SITE: (usize, usize) = (288, 16);
+const RED: (usize, usize) = (304, 16);
+const BLACK: (usize, usize) = (320, 16);
+const ANNOTATOR: (usize, usize) = (464, 16);
+const AUTHOR: (usize, usize) = (480, 16);
+
+const KEY_STREAM_MASK: &[u8; 32] = b"[(C) Copyright Mr. Dong Shiwei.]";
+
+// Piece order of the 32 squares in the header, Red first
+const PIECE_ORDER: [PieceType; 16] = [
+    PieceType::Chariot,
+    PieceType::Horse,
+    PieceType::Elephant,
+    PieceType::Advisor,
+    PieceType::General,
+    PieceType::Advisor,
+    PieceType::Elephant,
+    PieceType::Horse,
+    PieceType::Chariot,
+    PieceType::Cannon,
+    PieceType::Cannon,
+    PieceType::Soldier,
+    PieceType::Soldier,
+    PieceType::Soldier,
+    PieceType::Soldier,
+    PieceType::Soldier,
+];
+
+/// A game read from an XQF file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XqfGame {
+    pub version: u8,
+    pub title: String,
+    pub event: String,
+    pub date: String,
+    pub site: String,
+    pub red: String,
+    pub black: String,
+    pub annotator: String,
+    pub author: String,
+    /// "1-0", "0-1", "1/2-1/2" or "*" when unknown
+    pub result: String,
+    /// Position before the first move
+    pub start: GameState,
+    /// The move tree; node 0 is the start position and has no move
+    pub nodes: Vec<XqfNode>,
+}
+
+/// A node of the move tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XqfNode {
+    /// The move leading to this node
+    pub mv: Option<Move>,
+    pub comment: String,
+    pub parent: Option<usize>,
+    /// Continuations, the main line first
+    pub children: Vec<usize>,
+}
+
+impl XqfGame {
+    /// The moves of the main line, following the first child at every node.
+    pub fn main_line(&self) -> Vec<Move> {
+        let mut moves = Vec::new();
+        let mut node = &self.nodes[0];
+        while let Some(&child) = node.children.first() {
+            node = &self.nodes[child];
+            moves.extend(node.mv);
+        }
+        moves
+    }
+
+    /// Replays the moves from the start position into the game tree,
+    /// variations included, ending at the end of the main line. Positions
+    /// are only judged at the end, so lines that went on past a repetition
+    /// still load. A decisive result from the header that the moves do not
+    /// reach on the board is applied as a resignation, a drawn one as an
+    /// agreed draw. Comments are kept as move annotations, the one before
+    /// the first move on the game tree.
+    pub fn to_manager(&self) -> Result<GameStateManager, ChessError> {
+        let mut manager = GameStateManager::from_state(self.start.clone());
+        manager.update_status();
+        manager
+            .tree
+            .set_comment(Some(self.nodes[0].comment.clone()));
+
+        // Parents always come before their children, and the main line
+        // before its variations
+        let mut tree_nodes = vec![ROOT; self.nodes.len()];
+        for (index, node) in self.nodes.iter().enumerate().skip(1) {
+            manager.goto_node(tree_nodes[node.parent.unwrap()])?;
+            manager.replay_move(node.mv.unwrap())?;
+            tree_nodes[index] = manager.current_node();
+            if !node.comment.is_empty() {
+                let annotation = Annotation {
+                    comment: Some(node.comment.clone()),
+                    ..Annotation::default()
+                };
+                manager.annotate_move(tree_nodes[index], annotation)?;
+            }
+        }
+        let end = manager.tree.main_line().last().copied().unwrap_or(ROOT);
+        manager.goto_node(end)?;
+
+        if !manager.state.is_ended {
+            manager.update_status();
+        }
+        if !manager.state.is_ended {
+            match self.result.as_str() {
+                "1-0" => manager.resign(Color::Black)?,
+                "0-1" => manager.resign(Color::Red)?,
+                "1/2-1/2" => manager.agree_draw()?,
+                _ => {}
+            }
+        }
+        Ok(manager)
+    }
+}
+
+/// Reads an XQF file, decrypting it if needed.
+pub fn read(data: &[u8]) -> Result<XqfGame, ChessError> {
+    if data.len() < HEADER_LEN || &data[..2] != b"XQ" {
+        return Err(invalid("not an XQF file"));
+    }
+    let header = &data[..HEADER_LEN];
+    let version = header[VERSION];
+    let keys = Keys::new(header);
+
+    // Version 1.2 rotates the piece list by the piece key
+    let mut squares = [0u8; 32];
+    for (i, &byte) in header[PIECES..PIECES + 32].iter().enumerate() {
+        let slot = if version < 12 {
+            i
+        } else {
+            (keys.piece as usize + 1 + i) % 32
+        };
+        squares[slot] = byte.wrapping_sub(keys.piece);
+    }
+
+    let mut board = Board::new();
+    for (i, &square) in squares.iter().enumerate() {
+        if let Some(square) = decode_square(square) {
+            let color = if i < 16 { Color::Red } else { Color::Black };
+            board.set_piece_at(square, Some(Piece::new(PIECE_ORDER[i % 16], color)));
+        }
+    }
+
+    let mut reader = RecordReader {
+        data,
+        pos: HEADER_LEN,
+        keys,
+        version,
+        key_index: 0,
+    };
+    let (nodes, first_mover) = read_tree(&mut reader, &board)?;
+
+    // The side to move is whoever makes the first move; the header flag is
+    // only trusted for games without moves
+    let side = first_mover.unwrap_or(if header[WHO_PLAYS] == 1 {
+        Color::Black
+    } else {
+        Color::Red
+    });
+    let start = GameState::from_position(board, side)?;
+
+    let result = match header[RESULT] {
+        1 => "1-0",
+        2 => "0-1",
+        3 => "1/2-1/2",
+        _ => "*",
+    };
+
+    Ok(XqfGame {
+        version,
+        title: text_field(header, TITLE),
+        event: text_field(header, EVENT),
+        date: text_field(header, DATE),
+        site: text_field(header, SITE),
+        red: text_field(header, RED),
+        black: text_field(header, BLACK),
+        annotator: text_field(header, ANNOTATOR),
+        author: text_field(header, AUTHOR),
+        result: result.to_string(),
+        start,
+        nodes,
+    })
+}
+
+// Reads the records and checks every move against the position it is
+// played in. Also returns the color of the first move.
+fn read_tree(
+    reader: &mut RecordReader,
+    start: &Board,
+) -> Result<(Vec<XqfNode>, Option<Color>), ChessError> {
+    let root = reader.read_record()?;
+    let mut nodes = vec![XqfNode {
+        mv: None,
+        comment: root.comment,
+        parent: None,
+        children: Vec::new(),
+    }];
+    // Position and side to move at every node; the root's side is set by
+    // the first move
+    let mut boards = vec![start.clone()];
+    let mut sides = vec![None];
+
+    // Nodes whose alternative comes after their own subtree
+    let mut pending_branches = Vec::new();
+    let (mut has_next, mut has_branch) = (root.has_next, false);
+    let mut current = 0;
+
+    loop {
+        if has_branch {
+            pending_branches.push(current);
+        }
+        let parent = if has_next {
+            current
+        } else {
+            match pending_branches.pop() {
+                Some(node) => nodes[node].parent.unwrap(),
+                None => break,
+            }
+        };
+
+        let record = reader.read_record()?;
+        let mv = record.mv.ok_or_else(|| invalid("move off the board"))?;
+        let mut board = boards[parent].clone();
+        let color = match board.piece_at(mv.from()) {
+            Some(piece) => piece.color,
+            None => return Err(invalid("move from an empty square")),
+        };
+        if *sides[parent].get_or_insert(color) != color
+            || rules::validate_legal_move(&mut board, mv, color).is_err()
+        {
+            return Err(ChessError::InvalidXqf(format!(
+                "illegal move {}",
+                mv.to_iccs()
+            )));
+        }
+        board.make_move(mv);
+
+        current = nodes.len();
+        nodes.push(XqfNode {
+            mv: Some(mv),
+            comment: record.comment,
+            parent: Some(parent),
+            children: Vec::new(),
+        });
+        nodes[parent].children.push(current);
+        boards.push(board);
+        sides.push(Some(color.opponent()));
+        (has_next, has_branch) = (record.has_next, record.has_branch);
+    }
+
+    Ok((nodes, sides[0]))
+}
+
+// Keys derived from the header of encrypted files; all zero for older ones
+struct Keys {
+    piece: u8,
+    from: u8,
+    to: u8,
+    comment: u32,
+    stream: [u8; 32],
+}
+
+impl Keys {
+    fn new(header: &[u8]) -> Self {
+        if header[VERSION] < 11 {
+            return Self {
+                piece: 0,
+                from: 0,
+                to: 0,
+                comment: 0,
+                stream: [0; 32],
+            };
+        }
+
+        let square_plus = |x: u8| (x as u32 * x as u32 * 54 + 221) as u8;
+        let piece = square_plus(header[KEY_XY]).wrapping_mul(header[KEY_XY]);
+        let from = square_plus(header[KEY_XY_FROM]).wrapping_mul(piece);
+        let to = square_plus(header[KEY_XY_TO]).wrapping_mul(from);
+        let comment = (header[KEY_SUM] as u32 * 256 + header[KEY_XY] as u32) % 32000 + 767;
+
+        let mut stream = [0; 32];
+        for (i, key) in stream.iter_mut().enumerate() {
+            let arg = header[KEY_OR + i % 4] | (header[KEY_SUM + i % 4] & header[KEY_MASK]);
+            *key = arg & KEY_STREAM_MASK[i];
+        }
+
+        Self {
+            piece,
+            from,
+            to,
+            comment,
+            stream,
+        }
+    }
+}
+
+struct Record {
+    mv: Option<Move>,
+    comment: String,
+    has_next: bool,
+    has_branch: bool,
+}
+
+struct RecordReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    keys: Keys,
+    version: u8,
+    key_index: usize,
+}
+
+impl RecordReader<'_> {
+    fn read_record(&mut self) -> Result<Record, ChessError> {
+        let bytes = self.read_bytes(4)?;
+        let (from, to, tag) = (bytes[0], bytes[1], bytes[2]);
+
+        let (has_next, has_branch, comment_len) = if self.version < 11 {
+            // Old files always store the comment length
+            let len = self.read_u32()?;
+            (tag & 0xf0 != 0, tag & 0x0f != 0, len)
+        } else {
+            let len = if tag & 0x20 != 0 {
+                self.read_u32()?.wrapping_sub(self.keys.comment)
+            } else {
+                0
+            };
+            (tag & 0x80 != 0, tag & 0x40 != 0, len)
+        };
+
+        let from = decode_square(from.wrapping_sub(24).wrapping_sub(self.keys.from));
+        let to = decode_square(to.wrapping_sub(32).wrapping_sub(self.keys.to));
+        let comment = if comment_len > 0 {
+            let len = usize::try_from(comment_len).map_err(|_| invalid("bad comment length"))?;
+            decode_text(&self.read_bytes(len)?)
+        } else {
+            String::new()
+        };
+
+        Ok(Record {
+            mv: from.zip(to).map(|(from, to)| Move::new(from, to)),
+            comment,
+            has_next,
+            has_branch,
+        })
+    }
+
+    fn read_u32(&mut self) -> Result<u32, ChessError> {
+        let bytes = self.read_bytes(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn read_bytes(&mut self, len: usize) -> Result<Vec<u8>, ChessError> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|&end| end <= self.data.len())
+            .ok_or_else(|| invalid("unexpected end of file"))?;
+        let mut bytes = self.data[self.pos..end].to_vec();
+        for byte in &mut bytes {
+            *byte = byte.wrapping_sub(self.keys.stream[self.key_index]);
+            self.key_index = (self.key_index + 1) % 32;
+        }
+        self.pos = end;
+        Ok(bytes)
+    }
+}
+
+// Squares are stored as file * 10 + rank, counted from Red's bottom left
+fn decode_square(byte: u8) -> Option<Square> {
+    if byte >= 90 {
+        return None;
+    }
+    Square::from_xy((byte / 10) as usize, 9 - (byte % 10) as usize)
+}
+
+fn text_field(header: &[u8], (offset, size): (usize, usize)) -> String {
+    let len = (header[offset] as usize).min(size - 1);
+    decode_text(&header[offset + 1..offset + 1 + len])
+}
+
+fn decode_text(bytes: &[u8]) -> String {
+    let (text, _) = GB18030.decode_without_bom_handling(bytes);
+    text.trim_end_matches('\0').to_string()
+}
+
+fn invalid(reason: &str) -> ChessError {
+    ChessError::InvalidXqf(reason.to_string())
+}
+
+impl GameStateManager {
+    /// Starts a game from the main line of an XQF file.
+    pub fn from_xqf(data: &[u8]) -> Result<Self, ChessError> {
+        read(data)?.to_manager()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Termination;
+
+    // (from, to, comment, has_next, has_branch) in ICCS, the root first
+    type TestRecord<'a> = (&'a str, &'a str, &'a [u8], bool, bool);
+
+    // Squares of the standard start position in header order
+    fn start_squares() -> [u8; 32] {
+        let red = [
+            (0, 9),
+            (1, 9),
+            (2, 9),
+            (3, 9),
+            (4, 9),
+            (5, 9),
+            (6, 9),
+            (7, 9),
+            (8, 9),
+            (1, 7),
+            (7, 7),
+            (0, 6),
+            (2, 6),
+            (4, 6),
+            (6, 6),
+            (8, 6),
+        ];
+        let mut squares = [0; 32];
+        for (i, &(x, y)) in red.iter().enumerate() {
+            squares[i] = encode_xy(x, y);
+            squares[i + 16] = encode_xy(x, 9 - y);
+        }
+        squares
+    }
+
+    fn encode_xy(x: usize, y: usize) -> u8 {
+        (x * 10 + 9 - y) as u8
+    }
+
+    fn encode_square(iccs: &str) -> u8 {
+        if iccs.is_empty() {
+            return 0;
+        }
+        let square = crate::iccs::parse_square(iccs).unwrap();
+        encode_xy(square.x(), square.y())
+    }
+
+    // Writes a file the way XQStudio does, encrypting from version 1.1 on
+    fn write(
+        version: u8,
+        key_bytes: [u8; 8],
+        squares: [u8; 32],
+        records: &[TestRecord],
+    ) -> Vec<u8> {
+        let mut header = vec![0u8; HEADER_LEN];
+        header[..2].copy_from_slice(b"XQ");
+        header[VERSION] = version;
+        header[KEY_MASK] = 0x5a;
+        header[KEY_OR..KEY_OR + 8].copy_from_slice(&key_bytes);
+        header[RESULT] = 1;
+        let title = [0xba, 0xc3, 0xc6, 0xe5];
+        header[TITLE.0] = title.len() as u8;
+        header[TITLE.0 + 1..TITLE.0 + 1 + title.len()].copy_from_slice(&title);
+        header[RED.0] = 3;
+        header[RED.0 + 1..RED.0 + 4].copy_from_slice(b"Liu");
+
+        let keys = Keys::new(&header);
+        for i in 0..32 {
+            let slot = if version < 12 {
+                i
+            } else {
+                (keys.piece as usize + 1 + i) % 32
+            };
+            header[PIECES + i] = squares[slot].wrapping_add(keys.piece);
+        }
+
+        let mut body = Vec::new();
+        for &(from, to, comment, has_next, has_branch) in records {
+            let from = encode_square(from).wrapping_add(24).wrapping_add(keys.from);
+            let to = encode_square(to).wrapping_add(32).wrapping_add(keys.to);
+            if version < 11 {
+                let tag = if has_next { 0xf0 } else { 0 } | if has_branch { 0x0f } else { 0 };
+                body.extend([from, to, tag, 0]);
+                body.extend((comment.len() as u32).to_le_bytes());
+            } else {
+                let tag = if has_next { 0x80 } else { 0 }
+                    | if has_branch { 0x40 } else { 0 }
+                    | if comment.is_empty() { 0 } else { 0x20 };
+                body.extend([from, to, tag, 0]);
+                if !comment.is_empty() {
+                    body.extend((comment.len() as u32 + keys.comment).to_le_bytes());
+                }
+            }
+            body.extend(comment);
+        }
+        for (i, byte) in body.iter_mut().enumerate() {
+            *byte = byte.wrapping_add(keys.stream[i % 32]);
+        }
+
+        header.extend(body);
+        header
+    }
+
+    // Central cannon; Black answers with the horse, or with a central cannon
+    // in a side variation
+    const TREE: [TestRecord; 5] = [
+        ("", "", b"Opening", true, false),
+        ("h2", "e2", b"", true, false),
+        ("h9", "g7", b"Screen horse", true, true),
+        ("h0", "g2", b"", false, false),
+        ("b7", "e7", &[0xba, 0xc3, 0xc6, 0xe5], false, false),
+    ];
+
+    fn check_tree(game: &XqfGame) {
+        assert_eq!(game.title, "好棋");
+        assert_eq!(game.red, "Liu");
+        assert_eq!(game.result, "1-0");
+        assert_eq!(game.start, GameState::new());
+
+        assert_eq!(game.nodes.len(), 5);
+        assert_eq!(game.nodes[0].comment, "Opening");
+        assert_eq!(game.nodes[1].children, [2, 4]);
+        assert_eq!(game.nodes[2].comment, "Screen horse");
+        assert_eq!(game.nodes[4].mv, Some(Move::from_iccs("b7e7").unwrap()));
+        assert_eq!(game.nodes[4].parent, Some(1));
+        assert_eq!(game.nodes[4].comment, "好棋");
+
+        let main_line: Vec<String> = game.main_line().into_iter().map(Move::to_iccs).collect();
+        assert_eq!(main_line, ["h2e2", "h9g7", "h0g2"]);
+        let manager = game.to_manager().unwrap();
+        assert_eq!(manager.tree.comment(), Some("Opening"));
+        assert_eq!(manager.chinese_moves(), ["炮二平五", "马8进7", "马二进三"]);
+        // The header result is kept as a resignation
+        assert_eq!(manager.state.winner, Some(Color::Red));
+        assert_eq!(
+            manager.state.result.map(|result| result.termination),
+            Some(Termination::Resignation)
+        );
+        let black_move = manager.history.rounds[0].black_move.as_ref().unwrap();
+        assert_eq!(
+            black_move.annotation.comment.as_deref(),
+            Some("Screen horse")
+        );
+    }
+
+    #[test]
+    fn test_plain_file() {
+        let data = write(10, [0; 8], start_squares(), &TREE);
+        check_tree(&read(&data).unwrap());
+    }
+
+    #[test]
+    fn test_encrypted_files() {
+        let keys = [0x13, 0x57, 0x9b, 0xdf, 0x2e, 0x6a, 0xa6, 0xe2];
+        for version in [11, 12, 18] {
+            let data = write(version, keys, start_squares(), &TREE);
+            assert_ne!(&data[PIECES..PIECES + 32], &start_squares());
+            let game = read(&data).unwrap();
+            assert_eq!(game.version, version);
+            check_tree(&game);
+        }
+    }
+
+    #[test]
+    fn test_endgame_with_black_to_move() {
+        // Captured pieces are stored off the board
+        let mut squares = [0xff; 32];
+        squares[4] = encode_xy(3, 9);
+        squares[9] = encode_xy(5, 9);
+        squares[20] = encode_xy(4, 0);
+        let records: [TestRecord; 2] =
+            [("", "", b"", true, false), ("e9", "e8", b"", false, false)];
+        let game = read(&write(12, [7; 8], squares, &records)).unwrap();
+
+        assert_eq!(game.start.current_turn, Color::Black);
+        assert_eq!(game.start.to_fen(), "4k4/9/9/9/9/9/9/9/9/3K1C3 b - - 0 1");
+        let manager = GameStateManager::from_xqf(&write(12, [7; 8], squares, &records)).unwrap();
+        assert_eq!(manager.state.current_turn, Color::Red);
+    }
+
+    #[test]
+    fn test_game_going_on_after_a_repetition() {
+        // The start position comes up a third time after the eighth move,
+        // and the game goes on with the central cannon
+        let mut records: Vec<TestRecord> = vec![("", "", b"", true, false)];
+        for _ in 0..2 {
+            for (from, to) in [("h0", "g2"), ("h9", "g7"), ("g2", "h0"), ("g7", "h9")] {
+                records.push((from, to, b"", true, false));
+            }
+        }
+        records.push(("h2", "e2", b"", false, false));
+
+        let manager =
+            GameStateManager::from_xqf(&write(18, [9; 8], start_squares(), &records)).unwrap();
+        assert_eq!(manager.history.moves().count(), 9);
+        assert_eq!(manager.state.winner, Some(Color::Red));
+        assert_eq!(
+            manager.state.result.map(|result| result.termination),
+            Some(Termination::Resignation)
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(read(b"XQ"), Err(invalid("not an XQF file")));
+        let mut data = write(10, [0; 8], start_squares(), &TREE);
+        data[0] = b'Y';
+        assert_eq!(read(&data), Err(invalid("not an XQF file")));
+
+        let data = write(12, [1; 8], start_squares(), &TREE);
+        assert_eq!(
+            read(&data[..data.len() - 2]),
+            Err(invalid("unexpected end of file"))
+        );
+
+        let records: [TestRecord; 2] =
+            [("", "", b"", true, false), ("h2", "h8", b"", false, false)];
+        assert_eq!(
+            read(&write(12, [1; 8], start_squares(), &records)),
+            Err(ChessError::InvalidXqf("illegal move h2h8".to_string()))
+        );
+    }
+}
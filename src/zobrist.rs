@@ -0,0 +1,52 @@
+use crate::piece::{Color, Piece};
+
+/// Random keys for every (piece, square) pair, indexed by `piece_index` and
+/// `y * 9 + x`.
+static PIECE_KEYS: [[u64; 90]; 14] = generate_piece_keys();
+
+/// Key mixed into the position hash when Black is to move.
+pub const SIDE_KEY: u64 = splitmix64(0x5851_F42D_4C95_7F2D).1;
+
+/// Returns the key for a piece standing on the given square.
+pub fn piece_key(piece: Piece, x: usize, y: usize) -> u64 {
+    PIECE_KEYS[piece_index(piece)][y * 9 + x]
+}
+
+/// Returns the key for the side to move.
+pub fn side_key(color: Color) -> u64 {
+    match color {
+        Color::Red => 0,
+        Color::Black => SIDE_KEY,
+    }
+}
+
+fn piece_index(piece: Piece) -> usize {
+    piece.color as usize * 7 + piece.piece_type as usize
+}
+
+// Keys are generated at compile time with SplitMix64 so they are identical
+// across builds and platforms
+const fn splitmix64(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    (state, z ^ (z >> 31))
+}
+
+const fn generate_piece_keys() -> [[u64; 90]; 14] {
+    let mut keys = [[0u64; 90]; 14];
+    let mut state = 0x2545_F491_4F6C_DD1D;
+    let mut piece = 0;
+    while piece < 14 {
+        let mut square = 0;
+        while square < 90 {
+            let (next_state, key) = splitmix64(state);
+            state = next_state;
+            keys[piece][square] = key;
+            square += 1;
+        }
+        piece += 1;
+    }
+    keys
+}